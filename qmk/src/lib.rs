@@ -0,0 +1,430 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use rukeeb::handler::ALIASES;
+use serde_json::Value;
+
+// Long QMK names, without their `KC_` prefix, of the keycodes known to
+// `kc!` by a short name.
+const LONG_NAMES: &[(&str, &str)] = &[
+    ("ENTER", "ENT"),
+    ("ESCAPE", "ESC"),
+    ("BACKSPACE", "BSPC"),
+    ("SPACE", "SPC"),
+    ("MINUS", "MINS"),
+    ("EQUAL", "EQL"),
+    ("LEFT_BRACKET", "LBRC"),
+    ("RIGHT_BRACKET", "RBRC"),
+    ("BACKSLASH", "BSLS"),
+    ("NONUS_HASH", "NUHS"),
+    ("SEMICOLON", "SCLN"),
+    ("QUOTE", "QUOT"),
+    ("GRAVE", "GRV"),
+    ("COMMA", "COMM"),
+    ("SLASH", "SLSH"),
+    ("CAPS_LOCK", "CAPS"),
+    ("PRINT_SCREEN", "PSCR"),
+    ("SCROLL_LOCK", "SCRL"),
+    ("PAUSE", "PAUS"),
+    ("INSERT", "INS"),
+    ("PAGE_UP", "PGUP"),
+    ("DELETE", "DEL"),
+    ("PAGE_DOWN", "PGDN"),
+    ("RIGHT", "RGHT"),
+    ("NUM_LOCK", "NUM"),
+    ("KP_SLASH", "PSLS"),
+    ("KP_ASTERISK", "PAST"),
+    ("KP_MINUS", "PMNS"),
+    ("KP_PLUS", "PPLS"),
+    ("KP_ENTER", "PENT"),
+    ("KP_1", "P1"),
+    ("KP_2", "P2"),
+    ("KP_3", "P3"),
+    ("KP_4", "P4"),
+    ("KP_5", "P5"),
+    ("KP_6", "P6"),
+    ("KP_7", "P7"),
+    ("KP_8", "P8"),
+    ("KP_9", "P9"),
+    ("KP_0", "P0"),
+    ("KP_DOT", "PDOT"),
+    ("NONUS_BACKSLASH", "NUBS"),
+    ("APPLICATION", "APP"),
+    ("KB_POWER", "PWOR"),
+    ("KP_EQUAL", "PEQL"),
+    ("LEFT_CTRL", "LCTL"),
+    ("LEFT_SHIFT", "LSFT"),
+    ("LEFT_ALT", "LALT"),
+    ("LEFT_GUI", "LGUI"),
+    ("RIGHT_CTRL", "RCTL"),
+    ("RIGHT_SHIFT", "RSFT"),
+    ("RIGHT_ALT", "RALT"),
+    ("RIGHT_GUI", "RGUI"),
+    ("SYSTEM_POWER", "PWR"),
+    ("SYSTEM_SLEEP", "SLEP"),
+    ("SYSTEM_WAKE", "WAKE"),
+    ("AUDIO_MUTE", "MUTE"),
+    ("AUDIO_VOL_UP", "VOLU"),
+    ("AUDIO_VOL_DOWN", "VOLD"),
+    ("MEDIA_NEXT_TRACK", "MNXT"),
+    ("MEDIA_PREV_TRACK", "MPRV"),
+    ("MEDIA_STOP", "MSTP"),
+    ("MEDIA_PLAY_PAUSE", "MPLY"),
+    ("MS_U", "MS_UP"),
+    ("MS_D", "MS_DOWN"),
+    ("MS_L", "MS_LEFT"),
+    ("MS_R", "MS_RGHT"),
+    ("MS_RIGHT", "MS_RGHT"),
+    ("WH_U", "MS_WHLU"),
+    ("WH_D", "MS_WHLD"),
+    ("WH_L", "MS_WHLL"),
+    ("WH_R", "MS_WHLR"),
+    ("MS_WH_UP", "MS_WHLU"),
+    ("MS_WH_DOWN", "MS_WHLD"),
+    ("MS_WH_LEFT", "MS_WHLL"),
+    ("MS_WH_RIGHT", "MS_WHLR"),
+    ("BTN1", "MS_BTN1"),
+    ("BTN2", "MS_BTN2"),
+    ("BTN3", "MS_BTN3"),
+    ("BTN4", "MS_BTN4"),
+    ("BTN5", "MS_BTN5"),
+    ("ACL0", "MS_ACL0"),
+    ("ACL1", "MS_ACL1"),
+    ("ACL2", "MS_ACL2"),
+    ("QK_GRAVE_ESCAPE", "QK_GESC"),
+    ("QK_REPEAT_KEY", "QK_REP"),
+    ("QK_ALT_REPEAT_KEY", "QK_AREP"),
+    ("QK_LAYER_LOCK", "QK_LLCK"),
+    ("QK_UNICODE_MODE_LINUX", "UC_LINX"),
+    ("QK_UNICODE_MODE_WINDOWS", "UC_WIN"),
+    ("QK_UNICODE_MODE_WINCOMPOSE", "UC_WINC"),
+    ("QK_UNICODE_MODE_MACOS", "UC_MAC"),
+    ("QK_DYNAMIC_MACRO_RECORD_START_1", "DM_REC1"),
+    ("QK_DYNAMIC_MACRO_RECORD_START_2", "DM_REC2"),
+    ("QK_DYNAMIC_MACRO_PLAY_1", "DM_PLY1"),
+    ("QK_DYNAMIC_MACRO_PLAY_2", "DM_PLY2"),
+    ("QK_DYNAMIC_MACRO_RECORD_STOP", "DM_RSTP"),
+    ("QK_SWAP_HANDS_MOMENTARY_ON", "SH_MON"),
+    ("QK_SWAP_HANDS_ON", "SH_ON"),
+    ("QK_SWAP_HANDS_OFF", "SH_OFF"),
+    ("QK_SWAP_HANDS_TOGGLE", "SH_TOGG"),
+    ("QK_SWAP_HANDS_ONE_SHOT", "SH_OS"),
+    ("QK_JOYSTICK_BUTTON_0", "JS_0"),
+    ("QK_JOYSTICK_BUTTON_1", "JS_1"),
+    ("QK_JOYSTICK_BUTTON_2", "JS_2"),
+    ("QK_JOYSTICK_BUTTON_3", "JS_3"),
+    ("QK_JOYSTICK_BUTTON_4", "JS_4"),
+    ("QK_JOYSTICK_BUTTON_5", "JS_5"),
+    ("QK_JOYSTICK_BUTTON_6", "JS_6"),
+    ("QK_JOYSTICK_BUTTON_7", "JS_7"),
+    ("QK_DYNAMIC_TAPPING_TERM_UP", "DT_UP"),
+    ("QK_DYNAMIC_TAPPING_TERM_DOWN", "DT_DOWN"),
+    ("QK_DYNAMIC_TAPPING_TERM_PRINT", "DT_PRNT"),
+];
+
+// Layer keycodes taking a layer number, and the macros they become.
+const LAYER_KEYS: &[(&str, &str)] = &[
+    ("MO", "lymo"),
+    ("TO", "lytp"),
+    ("TG", "lytgtp"),
+    ("DF", "lydftp"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Json(String),
+    // Missing layers, or layers that are not lists of keycodes of one size.
+    Layers,
+    // The keys per row do not divide the keys of a layer.
+    Rows(usize, usize),
+    // Keycode without a `kc!` alias, or switching to a missing layer, at
+    // this layer and key.
+    Keycode(usize, usize, String),
+    // Transparent key at this layer and key, without one layer that is
+    // always active right below it.
+    Transparent(usize, usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Json(error) => write!(f, "invalid keymap.json: {error}"),
+            Error::Layers => write!(f, "layers must be lists of keycodes of the same size"),
+            Error::Rows(keys, cols) => write!(f, "{keys} keys do not fit in rows of {cols}"),
+            Error::Keycode(layer, i, code) => {
+                write!(f, "layer {layer}, key {i}: unsupported keycode {code}")
+            }
+            Error::Transparent(layer, i) => {
+                write!(
+                    f,
+                    "layer {layer}, key {i}: no single layer below a transparent key"
+                )
+            }
+        }
+    }
+}
+
+enum Key {
+    Transparent,
+    Expr(String),
+}
+
+// Splits a keycode such as `MO(1)` into its function and layer.
+fn layer_key(code: &str) -> Option<(&str, usize)> {
+    let (function, layer) = code.strip_suffix(')')?.split_once('(')?;
+    Some((function, layer.trim().parse().ok()?))
+}
+
+// Translates a keycode, with layer keycodes limited to `layers` layers.
+fn translate(code: &str, layers: usize) -> Option<Key> {
+    match code {
+        "_______" | "KC_TRNS" | "KC_TRANSPARENT" => return Some(Key::Transparent),
+        "XXXXXXX" => return Some(Key::Expr("kc!(NO)".into())),
+        _ => {}
+    }
+    if code.ends_with(')') {
+        let (function, layer) = layer_key(code).filter(|(_, l)| *l < layers)?;
+        let (_, name) = LAYER_KEYS.iter().find(|(qmk, _)| *qmk == function)?;
+        return Some(Key::Expr(format!("{name}!({layer})")));
+    }
+
+    let name = code.strip_prefix("KC_").unwrap_or(code);
+    let name = LONG_NAMES
+        .iter()
+        .find(|(long, _)| *long == name)
+        .map_or(name, |(_, short)| short);
+    ALIASES
+        .contains(&name)
+        .then(|| Key::Expr(format!("kc!({name})")))
+}
+
+// The layer that transparent keys of each layer fall through to. That is
+// the nearest active layer below it, which depends on how it is reached:
+// the layer holding MO or TG, or the default layer under TO. There is none
+// when the layer can be reached with several layers below it, when it can
+// be the default layer, or when no keycode switches to it.
+fn below(layers: &[Vec<&str>]) -> Vec<Option<usize>> {
+    let keys = || {
+        layers.iter().enumerate().flat_map(|(k, layer)| {
+            layer
+                .iter()
+                .filter_map(move |code| Some((k, layer_key(code)?)))
+        })
+    };
+    let defaults: BTreeSet<usize> = keys()
+        .filter(|(_, (function, _))| *function == "DF")
+        .map(|(_, (_, layer))| layer)
+        .chain([0])
+        .collect();
+    (0..layers.len())
+        .map(|l| {
+            let mut under = BTreeSet::new();
+            for (k, (function, _)) in keys().filter(|(k, (_, layer))| *layer == l && *k != l) {
+                match function {
+                    "MO" | "TG" => {
+                        under.insert((k < l).then_some(k));
+                    }
+                    "TO" => under.extend(defaults.iter().map(|d| (*d < l).then_some(*d))),
+                    _ => {}
+                }
+            }
+            if defaults.contains(&l) {
+                under.insert(None);
+            }
+            match under.len() {
+                1 => under.pop_first().flatten(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Translates the layers of a QMK keymap.json into a `keys!` invocation,
+// with `cols` keys per row or all of them on one row. As `keys!` counts
+// down the rows first, key `i` goes in row `i % rows` so that it keeps
+// its index. A transparent key takes the key of the layer it falls
+// through to, see `below`. All unsupported keycodes are reported at once.
+pub fn convert(json: &str, cols: Option<usize>) -> Result<String, Vec<Error>> {
+    let keymap: Value = serde_json::from_str(json).map_err(|e| vec![Error::Json(e.to_string())])?;
+    let layers: Vec<Vec<&str>> = keymap["layers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|layer| {
+            let keys = layer.as_array()?;
+            keys.iter().map(Value::as_str).collect::<Option<Vec<_>>>()
+        })
+        .collect::<Option<_>>()
+        .ok_or(vec![Error::Layers])?;
+    let keys = layers.first().map_or(0, Vec::len);
+    if keys == 0 || layers.iter().any(|layer| layer.len() != keys) {
+        return Err(vec![Error::Layers]);
+    }
+    let cols = cols.unwrap_or(keys);
+    if cols == 0 || !keys.is_multiple_of(cols) {
+        return Err(vec![Error::Rows(keys, cols)]);
+    }
+
+    let mut errors = Vec::new();
+    let below = below(&layers);
+    let mut translated: Vec<Vec<String>> = Vec::new();
+    for (l, layer) in layers.iter().enumerate() {
+        let mut row = Vec::new();
+        for (i, code) in layer.iter().enumerate() {
+            let expr = match (translate(code, layers.len()), below[l]) {
+                (Some(Key::Expr(expr)), _) => expr,
+                (Some(Key::Transparent), Some(below)) => translated[below][i].clone(),
+                (Some(Key::Transparent), None) => {
+                    errors.push(Error::Transparent(l, i));
+                    String::new()
+                }
+                (None, _) => {
+                    errors.push(Error::Keycode(l, i, code.to_string()));
+                    String::new()
+                }
+            };
+            row.push(expr);
+        }
+        translated.push(row);
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut output = String::from("keys!(\n");
+    for layer in translated {
+        output.push_str("    [\n");
+        let rows = keys / cols;
+        for r in 0..rows {
+            let row: Vec<&str> = layer
+                .iter()
+                .skip(r)
+                .step_by(rows)
+                .map(String::as_str)
+                .collect();
+            output.push_str(&format!("        {};\n", row.join(", ")));
+        }
+        output.push_str("    ],\n");
+    }
+    output.push(')');
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_aliases() {
+        assert!(["NO", "A", "ENT", "QK_REP", "DT_PRNT"]
+            .iter()
+            .all(|alias| ALIASES.contains(alias)));
+        assert!(!ALIASES.contains(&"Keyboard"));
+        for (long, short) in LONG_NAMES {
+            assert!(ALIASES.contains(short), "{long} -> {short}");
+        }
+    }
+
+    #[test]
+    fn test_fixture() {
+        let json = include_str!("../tests/fixtures/keymap.json");
+        let expected = "keys!(
+    [
+        kc!(NUM), kc!(P1), kc!(PENT);
+        kc!(APP), kc!(NUBS), lymo!(1);
+        kc!(QK_GESC), kc!(MS_BTN1), kc!(MS_WHLU);
+    ],
+    [
+        kc!(DM_REC1), kc!(DM_PLY1), kc!(PENT);
+        kc!(DT_UP), kc!(UC_MAC), lymo!(1);
+        kc!(PWR), kc!(MS_RGHT), kc!(JS_0);
+    ],
+)";
+        assert_eq!(convert(json, Some(3)).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_convert() {
+        let json = r#"{
+            "keyboard": "test",
+            "layout": "LAYOUT",
+            "layers": [
+                ["KC_A", "KC_ENTER", "MO(1)", "XXXXXXX"],
+                ["KC_1", "_______", "KC_TRNS", "QK_REP"]
+            ]
+        }"#;
+        let expected = "keys!(
+    [
+        kc!(A), lymo!(1);
+        kc!(ENT), kc!(NO);
+    ],
+    [
+        kc!(1), lymo!(1);
+        kc!(ENT), kc!(QK_REP);
+    ],
+)";
+        assert_eq!(convert(json, Some(2)).unwrap(), expected);
+        assert_eq!(convert(json, Some(3)), Err(vec![Error::Rows(4, 3)]));
+    }
+
+    #[test]
+    fn test_transparent() {
+        let json = r#"{"layers": [
+            ["KC_A", "MO(1)", "TO(3)", "KC_B"],
+            ["KC_1", "_______", "MO(2)", "_______"],
+            ["_______", "KC_X", "_______", "_______"],
+            ["_______", "KC_C", "KC_D", "KC_E"]
+        ]}"#;
+        let expected = "keys!(
+    [
+        kc!(A), lymo!(1), lytp!(3), kc!(B);
+    ],
+    [
+        kc!(1), lymo!(1), lymo!(2), kc!(B);
+    ],
+    [
+        kc!(1), kc!(X), lymo!(2), kc!(B);
+    ],
+    [
+        kc!(A), kc!(C), kc!(D), kc!(E);
+    ],
+)";
+        assert_eq!(convert(json, None).unwrap(), expected);
+
+        // Reached over two layers, the default layer, and never reached
+        let json = r#"{"layers": [
+            ["KC_A", "MO(1)", "DF(2)"],
+            ["_______", "KC_B", "KC_C"],
+            ["KC_D", "_______", "TG(1)"],
+            ["_______", "KC_E", "KC_F"]
+        ]}"#;
+        assert_eq!(
+            convert(json, None),
+            Err(vec![
+                Error::Transparent(1, 0),
+                Error::Transparent(2, 1),
+                Error::Transparent(3, 0),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let json =
+            r#"{"layers": [["KC_TRNS", "KC_NOPE", "TG(1)"], ["LT(1, KC_A)", "KC_B", "MO(2)"]]}"#;
+        assert_eq!(
+            convert(json, None),
+            Err(vec![
+                Error::Transparent(0, 0),
+                Error::Keycode(0, 1, "KC_NOPE".into()),
+                Error::Keycode(1, 0, "LT(1, KC_A)".into()),
+                Error::Keycode(1, 2, "MO(2)".into()),
+            ])
+        );
+        assert_eq!(
+            convert(r#"{"layers": [["KC_A"], ["KC_A", "KC_B"]]}"#, None),
+            Err(vec![Error::Layers])
+        );
+        assert!(matches!(convert("{", None), Err(e) if matches!(e[0], Error::Json(_))));
+    }
+}
@@ -0,0 +1,41 @@
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+// Prints the `keys!` invocation for a QMK keymap.json.
+// Usage: rukeeb-qmk <keymap.json> [keys per row]
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (path, cols) = match args.as_slice() {
+        [path] => (path, None),
+        [path, cols] => match cols.parse() {
+            Ok(cols) => (path, Some(cols)),
+            Err(_) => {
+                eprintln!("keys per row must be a number: {cols}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("usage: rukeeb-qmk <keymap.json> [keys per row]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(error) => {
+            eprintln!("{path}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match rukeeb_qmk::convert(&json, cols) {
+        Ok(keys) => {
+            println!("{keys}");
+            ExitCode::SUCCESS
+        }
+        Err(errors) => {
+            errors.iter().for_each(|error| eprintln!("{path}: {error}"));
+            ExitCode::FAILURE
+        }
+    }
+}
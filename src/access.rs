@@ -0,0 +1,196 @@
+use crate::debouncer::Counter;
+use crate::event::Event;
+use crate::keymap::Reports;
+use crate::mode::Modes;
+use crate::report::{Keyboard, Modifiers, Report};
+
+// Slow keys and bounce keys.
+// With slow keys, a press is accepted once the key has been held for `slow`
+// ticks. With bounce keys, a press within `bounce` ticks of the last release
+// is ignored until the key is released again. Accepted events are counted
+// anew, so durations start when the press is accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filters<const N: usize> {
+    slow: usize,
+    bounce: usize,
+    keys: [(Counter, bool); N],
+}
+
+impl<const N: usize> Default for Filters<N> {
+    fn default() -> Filters<N> {
+        Filters::new(300, 300)
+    }
+}
+
+impl<const N: usize> Filters<N> {
+    pub const fn new(slow: usize, bounce: usize) -> Filters<N> {
+        Filters {
+            slow,
+            bounce,
+            keys: [(Counter::Released(0), false); N],
+        }
+    }
+
+    pub fn set_slow(&mut self, slow: usize) {
+        self.slow = slow;
+    }
+
+    pub fn set_bounce(&mut self, bounce: usize) {
+        self.bounce = bounce;
+    }
+
+    pub fn filter(&mut self, events: &mut [Event; N], modes: Modes) {
+        let slow = match modes.contains(Modes::SLOW_KEYS) {
+            true => self.slow,
+            false => 0,
+        };
+        let bounce = match modes.contains(Modes::BOUNCE_KEYS) {
+            true => self.bounce,
+            false => 0,
+        };
+
+        for ((counter, rejected), event) in self.keys.iter_mut().zip(events) {
+            if let Event::Press(i) = event {
+                *rejected = *i < bounce;
+            }
+            let pressed = match (*event, *counter) {
+                _ if *rejected => false,
+                (Event::Press(_), _) => slow == 0,
+                (Event::Pressed(_), Counter::Pressed(_)) => true,
+                (Event::Pressed(i), Counter::Released(_)) => i >= slow,
+                _ => false,
+            };
+            *event = match pressed {
+                true => counter.press(),
+                false => counter.release(),
+            };
+        }
+    }
+}
+
+// Modifiers tapped on their own are latched and added to the reports until
+// the next key is released. Tapping a latched modifier again unlatches it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StickyModifiers {
+    latched: Modifiers,
+    held: Modifiers,
+    used: bool,
+    applied: bool,
+}
+
+impl StickyModifiers {
+    pub const fn new() -> StickyModifiers {
+        StickyModifiers {
+            latched: Modifiers::NONE,
+            held: Modifiers::NONE,
+            used: false,
+            applied: false,
+        }
+    }
+
+    pub fn latched(&self) -> Modifiers {
+        self.latched
+    }
+
+    pub fn apply(&mut self, reports: &mut Reports) {
+        let held = Modifiers::from_reports(reports);
+        let keys = reports.iter().any(|report| match report {
+            Report::Keyboard(key) => {
+                *key as u8 >= Keyboard::A as u8 && Modifiers::from_key(*key).is_empty()
+            }
+            _ => false,
+        });
+
+        // Modifiers used with a key are not latched.
+        self.used |= keys;
+        let released = self.held.difference(held);
+        if !self.used {
+            self.latched = Modifiers::from_bits(self.latched.bits() ^ released.bits());
+        }
+        if held.is_empty() {
+            self.used = false;
+        }
+        self.held = held;
+
+        match (keys, self.applied) {
+            (true, _) => self.applied = !self.latched.is_empty(),
+            (false, true) => {
+                self.latched = Modifiers::NONE;
+                self.applied = false;
+            }
+            (false, false) => {}
+        }
+        for key in self.latched.keys() {
+            if !reports.contains(&Report::Keyboard(key)) {
+                reports.push(Report::Keyboard(key)).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        let mut filters = Filters::<1>::new(3, 5);
+        let mut filter = |event: Event| {
+            let mut events = [event];
+            filters.filter(&mut events, Modes::SLOW_KEYS.union(Modes::BOUNCE_KEYS));
+            events[0]
+        };
+
+        // Slow keys
+        assert_eq!(filter(Event::Press(10)), Event::Released(1));
+        assert_eq!(filter(Event::Pressed(1)), Event::Released(2));
+        assert_eq!(filter(Event::Pressed(2)), Event::Released(3));
+        assert_eq!(filter(Event::Pressed(3)), Event::Press(3));
+        assert_eq!(filter(Event::Pressed(4)), Event::Pressed(1));
+        assert_eq!(filter(Event::Release(5)), Event::Release(1));
+
+        // Bounce keys
+        assert_eq!(filter(Event::Press(1)), Event::Released(1));
+        assert_eq!(filter(Event::Pressed(3)), Event::Released(2));
+        assert_eq!(filter(Event::Release(3)), Event::Released(3));
+        assert_eq!(filter(Event::Press(5)), Event::Released(4));
+        assert_eq!(filter(Event::Pressed(3)), Event::Press(4));
+    }
+
+    fn reports(keys: &[Keyboard]) -> Reports {
+        keys.iter().map(|key| Report::Keyboard(*key)).collect()
+    }
+
+    #[test]
+    fn test_sticky() {
+        let mut sticky = StickyModifiers::new();
+        let mut tick = |keys: &[Keyboard]| {
+            let mut output = reports(keys);
+            sticky.apply(&mut output);
+            output
+        };
+
+        tick(&[Keyboard::LeftShift]);
+        assert_eq!(tick(&[]), reports(&[Keyboard::LeftShift]));
+        assert_eq!(
+            tick(&[Keyboard::A]),
+            reports(&[Keyboard::A, Keyboard::LeftShift])
+        );
+        assert_eq!(
+            tick(&[Keyboard::A]),
+            reports(&[Keyboard::A, Keyboard::LeftShift])
+        );
+        assert_eq!(tick(&[]), reports(&[]));
+
+        // Used as a plain modifier.
+        tick(&[Keyboard::LeftShift, Keyboard::A]);
+        tick(&[Keyboard::LeftShift]);
+        assert_eq!(tick(&[]), reports(&[]));
+
+        // Tapped twice.
+        tick(&[Keyboard::LeftControl]);
+        tick(&[]);
+        tick(&[Keyboard::LeftControl]);
+        assert_eq!(tick(&[]), reports(&[]));
+    }
+}
@@ -0,0 +1,8 @@
+use crate::keymap::Reports;
+use crate::state::State;
+
+// Board specific behavior for `Report::Custom`.
+// Reports pushed to `reports` are sent along with the rest of the tick.
+pub trait Action: Sync {
+    fn act(&self, id: u8, state: &mut State, reports: &mut Reports);
+}
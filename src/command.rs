@@ -0,0 +1,219 @@
+use crate::event::Event;
+use crate::mode::Modes;
+use crate::state::{State, MAX_LAYERS};
+
+pub const PACKET_SIZE: usize = 32;
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// The version follows the command, status and length bytes of a response.
+const _: () = assert!(VERSION.len() < PACKET_SIZE - 2);
+
+// Requests and responses share the same layout: the command id, then its
+// arguments. Responses echo the id and carry a status byte before the data.
+pub type Packet = [u8; PACKET_SIZE];
+
+// Moves packets between the keymap and the host, e.g. over raw HID.
+pub trait Transport {
+    fn receive(&mut self) -> Option<Packet>;
+    fn send(&mut self, packet: &Packet);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Command {
+    // -> version string length, version string
+    Version = 0x01,
+    // -> highest active layer, active layers as u32
+    GetLayer = 0x02,
+    // layer -> highest active layer
+    SetLayer = 0x03,
+    // first byte -> key states as a bit per key, from key `8 * first byte`
+    KeyStates = 0x04,
+    // modes as u16 -> modes as u16
+    ToggleModes = 0x05,
+    // -> ticks as u32, presses as u32
+    Statistics = 0x06,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    Ok = 0x00,
+    UnknownCommand = 0x01,
+    InvalidArgument = 0x02,
+}
+
+impl Command {
+    pub fn from_u8(id: u8) -> Option<Command> {
+        match id {
+            0x01 => Some(Command::Version),
+            0x02 => Some(Command::GetLayer),
+            0x03 => Some(Command::SetLayer),
+            0x04 => Some(Command::KeyStates),
+            0x05 => Some(Command::ToggleModes),
+            0x06 => Some(Command::Statistics),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Statistics {
+    ticks: u32,
+    presses: u32,
+}
+
+impl Statistics {
+    pub fn count(&mut self, events: &[Event]) {
+        self.ticks = self.ticks.wrapping_add(1);
+        let presses = events.iter().filter(|e| matches!(e, Event::Press(_)));
+        self.presses = self.presses.wrapping_add(presses.count() as u32);
+    }
+
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    pub fn presses(&self) -> u32 {
+        self.presses
+    }
+}
+
+pub fn respond<const L: usize>(
+    request: &Packet,
+    state: &mut State,
+    events: &[Event],
+    statistics: &Statistics,
+) -> Packet {
+    let mut response = [0; PACKET_SIZE];
+    response[0] = request[0];
+    let (status, data) = response[1..].split_first_mut().unwrap();
+
+    *status = match Command::from_u8(request[0]) {
+        Some(Command::Version) => {
+            data[0] = VERSION.len() as u8;
+            data[1..1 + VERSION.len()].copy_from_slice(VERSION.as_bytes());
+            Status::Ok
+        }
+        Some(Command::GetLayer) => {
+            data[0] = state.layer() as u8;
+            data[1..5].copy_from_slice(&state.layers().to_le_bytes());
+            Status::Ok
+        }
+        Some(Command::SetLayer) if (request[1] as usize) < L.min(MAX_LAYERS) => {
+            state.set_layer(request[1] as usize);
+            data[0] = state.layer() as u8;
+            Status::Ok
+        }
+        Some(Command::KeyStates) => {
+            let first = request[1] as usize * 8;
+            let keys = events.iter().skip(first).take(data.len() * 8);
+            for (i, event) in keys.enumerate() {
+                if matches!(event, Event::Press(_) | Event::Pressed(_)) {
+                    data[i / 8] |= 1 << (i % 8);
+                }
+            }
+            Status::Ok
+        }
+        Some(Command::ToggleModes) => {
+            let modes = u16::from_le_bytes([request[1], request[2]]);
+            state.toggle(Modes::from_bits(modes));
+            data[..2].copy_from_slice(&state.modes().bits().to_le_bytes());
+            Status::Ok
+        }
+        Some(Command::Statistics) => {
+            data[..4].copy_from_slice(&statistics.ticks().to_le_bytes());
+            data[4..8].copy_from_slice(&statistics.presses().to_le_bytes());
+            Status::Ok
+        }
+        Some(Command::SetLayer) => Status::InvalidArgument,
+        None => Status::UnknownCommand,
+    } as u8;
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use heapless::spsc::Queue;
+    use heapless::Deque;
+
+    use super::*;
+    use crate::handler::Handle;
+    use crate::keymap::{BasicKeymap, Keymap};
+    use crate::processor::{KeyProcessor, Process};
+    use crate::report::Report;
+    use crate::*;
+
+    // Answers requests queued by the host side of the test.
+    #[derive(Default)]
+    struct Loopback {
+        requests: Deque<Packet, 4>,
+        responses: Deque<Packet, 4>,
+    }
+
+    impl Transport for Loopback {
+        fn receive(&mut self) -> Option<Packet> {
+            self.requests.pop_front()
+        }
+
+        fn send(&mut self, packet: &Packet) {
+            self.responses.push_back(*packet).unwrap();
+        }
+    }
+
+    impl Loopback {
+        fn request(&mut self, keymap: &mut BasicKeymap<2, 2>, bytes: &[u8]) -> Packet {
+            let mut packet = [0; PACKET_SIZE];
+            packet[..bytes.len()].copy_from_slice(bytes);
+            self.requests.push_back(packet).unwrap();
+            keymap.serve(self);
+            self.responses.pop_front().unwrap()
+        }
+    }
+
+    static mut Q: Queue<Report, 128> = Queue::new();
+    static KEYS: [[&dyn Handle; 2]; 2] = keys!([kc!(A), kc!(B);], [kc!(C), kc!(D);]);
+    static KH: KeyProcessor<2, 2> = KeyProcessor::new(KEYS);
+    static HANDLERS: [&'static dyn Process<2, 2>; 1] = [&KH];
+
+    #[test]
+    fn test_loopback() {
+        let (producer, _consumer) = unsafe { (*core::ptr::addr_of_mut!(Q)).split() };
+        let mut keymap: BasicKeymap<2, 2> = BasicKeymap::new(&HANDLERS, producer);
+        let mut host = Loopback::default();
+
+        let response = host.request(&mut keymap, &[Command::Version as u8]);
+        assert_eq!(response[..2], [Command::Version as u8, Status::Ok as u8]);
+        assert_eq!(&response[3..3 + response[2] as usize], VERSION.as_bytes());
+
+        let response = host.request(&mut keymap, &[Command::SetLayer as u8, 1]);
+        assert_eq!(
+            response[..3],
+            [Command::SetLayer as u8, Status::Ok as u8, 1]
+        );
+        let response = host.request(&mut keymap, &[Command::SetLayer as u8, 2]);
+        assert_eq!(response[1], Status::InvalidArgument as u8);
+        let mut request = [0; PACKET_SIZE];
+        request[..2].copy_from_slice(&[Command::SetLayer as u8, 40]);
+        let response = respond::<64>(&request, &mut State::new(), &[], &Statistics::default());
+        assert_eq!(response[1], Status::InvalidArgument as u8);
+        let response = host.request(&mut keymap, &[Command::GetLayer as u8]);
+        assert_eq!(response[2..7], [1, 2, 0, 0, 0]);
+
+        (0..10).for_each(|_| keymap.tick(&[false, true]));
+        let response = host.request(&mut keymap, &[Command::KeyStates as u8, 0]);
+        assert_eq!(response[2], 0b10);
+        let response = host.request(&mut keymap, &[Command::Statistics as u8]);
+        assert_eq!(response[2..10], [10, 0, 0, 0, 1, 0, 0, 0]);
+
+        let modes = Modes::GAMING.bits().to_le_bytes();
+        let response = host.request(
+            &mut keymap,
+            &[Command::ToggleModes as u8, modes[0], modes[1]],
+        );
+        assert_eq!(response[2..4], modes);
+
+        let response = host.request(&mut keymap, &[0x7F]);
+        assert_eq!(response[..2], [0x7F, Status::UnknownCommand as u8]);
+    }
+}
@@ -61,23 +61,30 @@ impl Counter {
     }
 }
 
+// Switches must stay in a new state for `dt` ticks before it is taken.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub struct Debouncer<const DT: usize> {
+pub struct Debouncer {
     buffer: Counter,
     counter: Counter,
+    dt: usize,
 }
 
-impl<const DT: usize> Debouncer<DT> {
-    pub fn new() -> Debouncer<DT> {
+impl Debouncer {
+    pub fn new(dt: usize) -> Debouncer {
         Debouncer {
+            dt,
             ..Default::default()
         }
     }
 
+    pub fn set_dt(&mut self, dt: usize) {
+        self.dt = dt;
+    }
+
     pub fn press(&mut self) -> Event {
         self.buffer.press();
         match self.buffer {
-            Counter::Pressed(i) if i >= DT => self.counter.press(),
+            Counter::Pressed(i) if i >= self.dt => self.counter.press(),
             _ => self.counter.remain(),
         }
     }
@@ -85,13 +92,13 @@ impl<const DT: usize> Debouncer<DT> {
     pub fn release(&mut self) -> Event {
         self.buffer.release();
         match self.buffer {
-            Counter::Released(i) if i >= DT => self.counter.release(),
+            Counter::Released(i) if i >= self.dt => self.counter.release(),
             _ => self.counter.remain(),
         }
     }
 }
 
-impl<const DT: usize> Debounce for Debouncer<DT> {
+impl Debounce for Debouncer {
     fn debounce(&mut self, switch: bool) -> Event {
         match switch {
             true => self.press(),
@@ -106,7 +113,7 @@ mod test {
 
     #[test]
     fn hold() {
-        let mut debouncer = Debouncer::<5>::new();
+        let mut debouncer = Debouncer::new(5);
         let mut event = Event::Released(0);
 
         (0..10).for_each(|_| {
@@ -127,7 +134,7 @@ mod test {
 
     #[test]
     fn wobble() {
-        let mut debouncer = Debouncer::<5>::new();
+        let mut debouncer = Debouncer::new(5);
         let mut event = Event::Released(0);
 
         (0..10).for_each(|_| {
@@ -144,7 +151,7 @@ mod test {
 
     #[test]
     fn no_debounce() {
-        let mut debouncer = Debouncer::<0>::new();
+        let mut debouncer = Debouncer::new(0);
         let mut event = Event::Released(1);
 
         (0..10).for_each(|_| {
@@ -1,9 +1,32 @@
-use crate::report::Report;
+use crate::lock::Lock;
+use crate::mode::Modes;
+use crate::mouse::MouseKey;
+use crate::processor::swap::Swap;
+use crate::recorder::Macro;
+use crate::repeat::Repeat;
+use crate::report::{Keyboard, Modifiers, Report};
+use crate::settings::Tune;
+use crate::unicode;
 
 #[derive(Clone, Copy)]
 pub enum Function {
     Report(Report),
+    // Press and release a key, with modifiers.
+    Tap(Keyboard, Modifiers),
     Layer(usize),
+    LayerOn(usize),
+    LayerOff(usize),
+    LayerToggle(usize),
+    DefaultLayer(usize),
+    Lock(Lock),
+    Toggle(Modes),
+    Swap(Swap),
+    Mouse(MouseKey),
+    Unicode(char),
+    UnicodeMode(unicode::Mode),
+    Macro(Macro),
+    Repeat(Repeat),
+    Tune(Tune),
 }
 
 // Function Macros
@@ -38,6 +61,128 @@ macro_rules! dk {
     };
 }
 
+// Key Tap
+#[macro_export]
+macro_rules! tp {
+    ($x: tt) => {
+        $crate::tp!(NONE, $x)
+    };
+    ($($m:ident)|+, $x: tt) => {
+        $crate::function::Function::Tap(
+            $crate::report::Keyboard::$x,
+            $crate::report::Modifiers::NONE$(.union($crate::report::Modifiers::$m))+,
+        )
+    };
+}
+
+// Gamepad Report
+#[macro_export]
+macro_rules! gp {
+    ($x: tt) => {
+        $crate::function::Function::Report($crate::report::Report::Gamepad(
+            $crate::gamepad::GamepadKey::$x.report(),
+        ))
+    };
+}
+
+// Custom Report
+#[macro_export]
+macro_rules! ct {
+    ($x: tt) => {
+        $crate::function::Function::Report($crate::report::Report::Custom($x))
+    };
+}
+
+// Mouse Key
+#[macro_export]
+macro_rules! ms {
+    ($x: tt) => {
+        $crate::function::Function::Mouse($crate::mouse::MouseKey::$x)
+    };
+}
+
+// Unicode Input
+#[macro_export]
+macro_rules! uc {
+    ($x: literal) => {
+        $crate::function::Function::Unicode($x)
+    };
+}
+
+// Unicode Input Mode
+#[macro_export]
+macro_rules! ucm {
+    ($x: tt) => {
+        $crate::function::Function::UnicodeMode($crate::unicode::Mode::$x)
+    };
+}
+
+// Dynamic Macro
+#[macro_export]
+macro_rules! dm {
+    ($($x: tt)*) => {
+        $crate::function::Function::Macro($crate::recorder::Macro::$($x)*)
+    };
+}
+
+// Layer Toggle
+#[macro_export]
+macro_rules! lytg {
+    ($x: tt) => {
+        $crate::function::Function::LayerToggle($x)
+    };
+}
+
+// Default Layer
+#[macro_export]
+macro_rules! lydf {
+    ($x: tt) => {
+        $crate::function::Function::DefaultLayer($x)
+    };
+}
+
+// Layer or Key Lock
+#[macro_export]
+macro_rules! lk {
+    ($x: tt) => {
+        $crate::function::Function::Lock($crate::lock::Lock::$x)
+    };
+}
+
+// Mode Toggle
+#[macro_export]
+macro_rules! md {
+    ($($x: ident)|+) => {
+        $crate::function::Function::Toggle(
+            $crate::mode::Modes::NONE$(.union($crate::mode::Modes::$x))+,
+        )
+    };
+}
+
+// Swap Hands
+#[macro_export]
+macro_rules! sh {
+    ($x: tt) => {
+        $crate::function::Function::Swap($crate::processor::swap::Swap::$x)
+    };
+}
+
+// Repeat Key
+#[macro_export]
+macro_rules! rp {
+    ($x: tt) => {
+        $crate::function::Function::Repeat($crate::repeat::Repeat::$x)
+    };
+}
+
+// Tapping Term Tuning
+#[macro_export]
+macro_rules! dt {
+    ($x: tt) => {
+        $crate::function::Function::Tune($crate::settings::Tune::$x)
+    };
+}
+
 // Layer Change
 #[macro_export]
 macro_rules! ly {
@@ -45,3 +190,19 @@ macro_rules! ly {
         $crate::function::Function::Layer($x)
     }};
 }
+
+// Layer On
+#[macro_export]
+macro_rules! lyon {
+    ($x: tt) => {
+        $crate::function::Function::LayerOn($x)
+    };
+}
+
+// Layer Off
+#[macro_export]
+macro_rules! lyof {
+    ($x: tt) => {
+        $crate::function::Function::LayerOff($x)
+    };
+}
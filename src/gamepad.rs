@@ -0,0 +1,96 @@
+use crate::keymap::Reports;
+use crate::report::{JoystickReport, Report};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadKey {
+    Button1,
+    Button2,
+    Button3,
+    Button4,
+    Button5,
+    Button6,
+    Button7,
+    Button8,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl GamepadKey {
+    // Report with only this button or axis direction.
+    pub const fn report(self) -> JoystickReport {
+        let (x, y, buttons) = match self {
+            GamepadKey::Button1 => (0, 0, 1 << 0),
+            GamepadKey::Button2 => (0, 0, 1 << 1),
+            GamepadKey::Button3 => (0, 0, 1 << 2),
+            GamepadKey::Button4 => (0, 0, 1 << 3),
+            GamepadKey::Button5 => (0, 0, 1 << 4),
+            GamepadKey::Button6 => (0, 0, 1 << 5),
+            GamepadKey::Button7 => (0, 0, 1 << 6),
+            GamepadKey::Button8 => (0, 0, 1 << 7),
+            GamepadKey::Up => (0, -i8::MAX, 0),
+            GamepadKey::Down => (0, i8::MAX, 0),
+            GamepadKey::Left => (-i8::MAX, 0, 0),
+            GamepadKey::Right => (i8::MAX, 0, 0),
+        };
+        JoystickReport { x, y, buttons }
+    }
+}
+
+// Merges the gamepad reports of one tick into the first of them.
+// Opposite directions cancel out.
+pub fn merge(reports: &mut Reports) {
+    let mut merged: Option<(i16, i16, u8)> = None;
+    reports.retain(|report| {
+        let Report::Gamepad(r) = report else {
+            return true;
+        };
+        let first = merged.is_none();
+        let (x, y, buttons) = merged.get_or_insert((0, 0, 0));
+        (*x, *y, *buttons) = (*x + r.x as i16, *y + r.y as i16, *buttons | r.buttons);
+        first
+    });
+
+    let Some((x, y, buttons)) = merged else {
+        return;
+    };
+    let clamp = |v: i16| v.clamp(-(i8::MAX as i16), i8::MAX as i16) as i8;
+    for report in reports.iter_mut() {
+        if let Report::Gamepad(r) = report {
+            *r = JoystickReport {
+                x: clamp(x),
+                y: clamp(y),
+                buttons,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut reports = Reports::new();
+        for key in [GamepadKey::Left, GamepadKey::Button2, GamepadKey::Up] {
+            reports.push(Report::Gamepad(key.report())).unwrap();
+        }
+        reports.push(Report::Custom(0)).unwrap();
+        reports
+            .push(Report::Gamepad(GamepadKey::Right.report()))
+            .unwrap();
+        merge(&mut reports);
+
+        let expected = JoystickReport {
+            x: 0,
+            y: -127,
+            buttons: 2,
+        };
+        assert_eq!(
+            reports.as_slice(),
+            &[Report::Gamepad(expected), Report::Custom(0)]
+        );
+    }
+}
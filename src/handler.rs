@@ -1,10 +1,19 @@
 use crate::event::Event;
 use crate::function::Function;
+use crate::state::State;
 
+pub mod autoshift;
+pub mod guard;
 pub mod holdtap;
+pub mod morph;
 
 pub trait Handle: Sync {
-    fn handle(&self, event: &Event) -> Option<&Function>;
+    fn handle(&self, event: &Event, state: &State) -> Option<Function>;
+
+    // Handler to use instead for the rest of the key press, decided on press.
+    fn resolve(&'static self, _event: &Event, _state: &State) -> Option<&'static dyn Handle> {
+        None
+    }
 }
 
 pub struct Hold(Function);
@@ -18,9 +27,9 @@ impl Hold {
 }
 
 impl Handle for Hold {
-    fn handle(&self, event: &Event) -> Option<&Function> {
+    fn handle(&self, event: &Event, _state: &State) -> Option<Function> {
         match event {
-            Event::Press(_) | Event::Pressed(_) => Some(&self.0),
+            Event::Press(_) | Event::Pressed(_) => Some(self.0),
             _ => None,
         }
     }
@@ -33,9 +42,9 @@ impl Tap {
 }
 
 impl Handle for Tap {
-    fn handle(&self, event: &Event) -> Option<&Function> {
+    fn handle(&self, event: &Event, _state: &State) -> Option<Function> {
         match event {
-            Event::Press(_) => Some(&self.0),
+            Event::Press(_) => Some(self.0),
             _ => None,
         }
     }
@@ -48,10 +57,10 @@ impl OnOff {
 }
 
 impl Handle for OnOff {
-    fn handle(&self, event: &Event) -> Option<&Function> {
+    fn handle(&self, event: &Event, _state: &State) -> Option<Function> {
         match event {
-            Event::Press(_) => Some(&self.0),
-            Event::Release(_) => Some(&self.1),
+            Event::Press(_) => Some(self.0),
+            Event::Release(_) => Some(self.1),
             _ => None,
         }
     }
@@ -99,6 +108,110 @@ macro_rules! dkhd {
     };
 }
 
+// Gamepad Report Hold
+#[macro_export]
+macro_rules! gphd {
+    ($x:tt) => {
+        $crate::handler::Hold::new($crate::gp!($x))
+    };
+}
+
+// Custom Report Tap
+#[macro_export]
+macro_rules! cttp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::ct!($x))
+    };
+}
+
+// Mouse Key Tap
+#[macro_export]
+macro_rules! mstp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::ms!($x))
+    };
+}
+
+// Mouse Key Hold
+#[macro_export]
+macro_rules! mshd {
+    ($x:tt) => {
+        $crate::handler::Hold::new($crate::ms!($x))
+    };
+}
+
+// Unicode Input Tap
+#[macro_export]
+macro_rules! uctp {
+    ($x:literal) => {
+        $crate::handler::Tap::new($crate::uc!($x))
+    };
+}
+
+// Unicode Input Mode Tap
+#[macro_export]
+macro_rules! ucmtp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::ucm!($x))
+    };
+}
+
+// Dynamic Macro Tap
+#[macro_export]
+macro_rules! dmtp {
+    ($($x:tt)*) => {
+        $crate::handler::Tap::new($crate::dm!($($x)*))
+    };
+}
+
+// Layer or Key Lock Tap
+#[macro_export]
+macro_rules! lktp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::lk!($x))
+    };
+}
+
+// Mode Toggle Tap
+#[macro_export]
+macro_rules! mdtp {
+    ($($x:ident)|+) => {
+        $crate::handler::Tap::new($crate::md!($($x)|+))
+    };
+}
+
+// Swap Hands Tap
+#[macro_export]
+macro_rules! shtp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::sh!($x))
+    };
+}
+
+// Swap Hands OnOff
+#[macro_export]
+macro_rules! shoo {
+    ($x0:tt, $x1:tt) => {
+        $crate::handler::OnOff::new($crate::sh!($x0), $crate::sh!($x1))
+    };
+}
+
+// Repeat Key Hold
+#[macro_export]
+macro_rules! rphd {
+    ($x:tt) => {
+        $crate::handler::Hold::new($crate::rp!($x))
+    };
+}
+
+// Tapping Term Tuning Tap
+#[macro_export]
+macro_rules! dttp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::dt!($x))
+    };
+}
+
 // Layer Tap
 #[macro_export]
 macro_rules! lytp {
@@ -115,6 +228,60 @@ macro_rules! lyoo {
     };
 }
 
+// Layer Toggle Tap
+#[macro_export]
+macro_rules! lytgtp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::lytg!($x))
+    };
+}
+
+// Default Layer Tap
+#[macro_export]
+macro_rules! lydftp {
+    ($x:tt) => {
+        $crate::handler::Tap::new($crate::lydf!($x))
+    };
+}
+
+// Momentary Layer
+#[macro_export]
+macro_rules! lymo {
+    ($x:tt) => {
+        $crate::handler::OnOff::new($crate::lyon!($x), $crate::lyof!($x))
+    };
+}
+
+// Every name accepted by `kc!`, passed to the macro `$then`.
+#[rustfmt::skip]
+macro_rules! kc_aliases {
+    ($then:ident) => {
+        $then![
+            NO, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, 1, 2,
+            3, 4, 5, 6, 7, 8, 9, 0, ENT, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, ESC,
+            BSPC, TAB, SPC, MINS, EQL, LBRC, RBRC, BSLS, NUHS, SCLN, QUOT, GRV, COMM, DOT, SLSH,
+            CAPS, PSCR, SCRL, PAUS, INS, HOME, PGUP, DEL, END, PGDN, RGHT, LEFT, DOWN, UP, NUM,
+            PSLS, PAST, PMNS, PPLS, PENT, P1, P2, P3, P4, P5, P6, P7, P8, P9, P0, PDOT, NUBS, APP,
+            PWOR, PEQL, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, LCTL, LSFT,
+            LALT, LGUI, RCTL, RSFT, RALT, RGUI, PWR, SLEP, WAKE, MUTE, VOLU, VOLD, MNXT, MPRV, MSTP,
+            MPLY, MS_UP, MS_DOWN, MS_LEFT, MS_RGHT, MS_WHLU, MS_WHLD, MS_WHLL, MS_WHLR, MS_BTN1,
+            MS_BTN2, MS_BTN3, MS_BTN4, MS_BTN5, MS_ACL0, MS_ACL1, MS_ACL2, UC_LINX, UC_WIN, UC_WINC,
+            UC_MAC, DM_REC1, DM_REC2, DM_PLY1, DM_PLY2, DM_RSTP, QK_GESC, QK_REP, QK_AREP, QK_LLCK,
+            QK_LOCK, SH_MON, SH_ON, SH_OFF, SH_TOGG, SH_OS, JS_0, JS_1, JS_2, JS_3, JS_4, JS_5,
+            JS_6, JS_7, DT_UP, DT_DOWN, DT_PRNT,
+        ]
+    };
+}
+
+macro_rules! names {
+    ($($x:tt),* $(,)?) => {
+        &[$(stringify!($x)),*]
+    };
+}
+
+// Names accepted by `kc!`, e.g. for translating keymaps of other firmware.
+pub const ALIASES: &[&str] = kc_aliases!(names);
+
 // Macro for QMK keycodes alias
 // kc!($x) = KC_$x
 #[macro_export]
@@ -245,9 +412,11 @@ macro_rules! kc {
     (RALT) => {$crate::kbhd!(RightAlt)};
     (RGUI) => {$crate::kbhd!(RightGUI)};
 
+    (QK_GESC) => {$crate::mm!(SFT | GUI, $crate::kb!(Escape), $crate::kb!(Grave))};
+
 
     // Desktop
-    (PWR)  => {$crate::dktp!(SystemPowerDown)};
+    (PWR)  => {$crate::gd!($crate::dk!(SystemPowerDown))};
     (SLEP) => {$crate::dktp!(SystemSleep)};
     (WAKE) => {$crate::dktp!(SystemWakeUp)};
 
@@ -260,6 +429,75 @@ macro_rules! kc {
     (MPRV) => {$crate::cuhd!(TrackingDecrement)};
     (MSTP) => {$crate::cutp!(Stop)};
     (MPLY) => {$crate::cutp!(PlayPause)};
+
+
+    // Mouse
+    (MS_UP)   => {$crate::mshd!(Up)};
+    (MS_DOWN) => {$crate::mshd!(Down)};
+    (MS_LEFT) => {$crate::mshd!(Left)};
+    (MS_RGHT) => {$crate::mshd!(Right)};
+    (MS_WHLU) => {$crate::mshd!(WheelUp)};
+    (MS_WHLD) => {$crate::mshd!(WheelDown)};
+    (MS_WHLL) => {$crate::mshd!(WheelLeft)};
+    (MS_WHLR) => {$crate::mshd!(WheelRight)};
+    (MS_BTN1) => {$crate::mshd!(Button1)};
+    (MS_BTN2) => {$crate::mshd!(Button2)};
+    (MS_BTN3) => {$crate::mshd!(Button3)};
+    (MS_BTN4) => {$crate::mshd!(Button4)};
+    (MS_BTN5) => {$crate::mshd!(Button5)};
+    (MS_ACL0) => {$crate::mshd!(Accel0)};
+    (MS_ACL1) => {$crate::mshd!(Accel1)};
+    (MS_ACL2) => {$crate::mshd!(Accel2)};
+
+
+    // Joystick
+    (JS_0) => {$crate::gphd!(Button1)};
+    (JS_1) => {$crate::gphd!(Button2)};
+    (JS_2) => {$crate::gphd!(Button3)};
+    (JS_3) => {$crate::gphd!(Button4)};
+    (JS_4) => {$crate::gphd!(Button5)};
+    (JS_5) => {$crate::gphd!(Button6)};
+    (JS_6) => {$crate::gphd!(Button7)};
+    (JS_7) => {$crate::gphd!(Button8)};
+
+
+    // Unicode
+    (UC_LINX) => {$crate::ucmtp!(Linux)};
+    (UC_WIN)  => {$crate::ucmtp!(Windows)};
+    (UC_WINC) => {$crate::ucmtp!(WinCompose)};
+    (UC_MAC)  => {$crate::ucmtp!(MacOS)};
+
+
+    // Dynamic Macro
+    (DM_REC1) => {$crate::dmtp!(Record(0))};
+    (DM_REC2) => {$crate::dmtp!(Record(1))};
+    (DM_PLY1) => {$crate::dmtp!(Play(0))};
+    (DM_PLY2) => {$crate::dmtp!(Play(1))};
+    (DM_RSTP) => {$crate::dmtp!(Stop)};
+
+
+    // Lock
+    (QK_LLCK) => {$crate::lktp!(Layer)};
+    (QK_LOCK) => {$crate::lktp!(Key)};
+
+
+    // Swap Hands
+    (SH_MON)  => {$crate::shoo!(On, Off)};
+    (SH_ON)   => {$crate::shtp!(On)};
+    (SH_OFF)  => {$crate::shtp!(Off)};
+    (SH_TOGG) => {$crate::shtp!(Toggle)};
+    (SH_OS)   => {$crate::shtp!(OneShot)};
+
+
+    // Repeat Key
+    (QK_REP)  => {$crate::rphd!(Last)};
+    (QK_AREP) => {$crate::rphd!(Alternate)};
+
+
+    // Tapping Term
+    (DT_UP)   => {$crate::dttp!(Up)};
+    (DT_DOWN) => {$crate::dttp!(Down)};
+    (DT_PRNT) => {$crate::dttp!(Print)};
 }
 
 #[cfg(test)]
@@ -272,15 +510,8 @@ mod test {
 
     #[test]
     fn test_kc() {
-        let _handlers: [&dyn crate::handler::Handle; 132] = test_kc![
-            NO, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, 1, 2,
-            3, 4, 5, 6, 7, 8, 9, 0, ENT, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, ENT,
-            ESC, BSPC, TAB, SPC, MINS, EQL, LBRC, RBRC, BSLS, NUHS, SCLN, QUOT, GRV, COMM, DOT,
-            SLSH, CAPS, PSCR, SCRL, PAUS, INS, HOME, PGUP, DEL, END, PGDN, RGHT, LEFT, DOWN, UP,
-            NUM, PSLS, PAST, PMNS, PPLS, PENT, P1, P2, P3, P4, P5, P6, P7, P8, P9, P0, PDOT, NUBS,
-            APP, PWOR, PEQL, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, LCTL,
-            LSFT, LALT, LGUI, RCTL, RSFT, RALT, RGUI, PWR, SLEP, WAKE, MUTE, VOLU, VOLD, MNXT,
-            MPRV, MSTP, MPLY,
-        ];
+        // A static, as in `keys!`, so that no alias borrows interior mutability.
+        static _HANDLERS: [&dyn crate::handler::Handle; super::ALIASES.len()] =
+            kc_aliases!(test_kc);
     }
 }
@@ -0,0 +1,86 @@
+use crate::event::Event;
+use crate::handler::{Function, Handle, Hold};
+use crate::report::{Keyboard, Modifiers, Report};
+use crate::state::State;
+
+// Taps `key` when released before `thold`, or taps it with Shift once held
+// for `thold`. Keys excluded in the state act as plain keys instead.
+pub struct AutoShift {
+    key: Keyboard,
+    thold: usize,
+    tap: Function,
+    hold: Function,
+    plain: Hold,
+}
+
+impl AutoShift {
+    pub const fn new(thold: usize, key: Keyboard) -> AutoShift {
+        AutoShift {
+            key,
+            thold,
+            tap: Function::Tap(key, Modifiers::NONE),
+            hold: Function::Tap(key, Modifiers::LSFT),
+            plain: Hold::new(Function::Report(Report::Keyboard(key))),
+        }
+    }
+}
+
+impl Handle for AutoShift {
+    fn handle(&self, event: &Event, _state: &State) -> Option<Function> {
+        match event {
+            Event::Pressed(i) if *i == self.thold => Some(self.hold),
+            Event::Release(i) if *i < self.thold => Some(self.tap),
+            _ => None,
+        }
+    }
+
+    fn resolve(&'static self, _event: &Event, state: &State) -> Option<&'static dyn Handle> {
+        match state.is_unshifted(self.key) {
+            true => Some(&self.plain),
+            false => None,
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! asft {
+    ($x:tt) => {
+        $crate::asft!(175, $x)
+    };
+    ($thold:literal, $x:tt) => {
+        $crate::handler::autoshift::AutoShift::new($thold, $crate::report::Keyboard::$x)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::Event;
+    use crate::function::Function;
+    use crate::handler::autoshift::AutoShift;
+    use crate::handler::Handle;
+    use crate::report::{Keyboard, Modifiers};
+    use crate::state::State;
+
+    static A: AutoShift = asft!(50, A);
+
+    fn tap(event: Event) -> Option<(Keyboard, Modifiers)> {
+        match A.handle(&event, &State::new()) {
+            Some(Function::Tap(key, modifiers)) => Some((key, modifiers)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_asft() {
+        assert_eq!(tap(Event::Press(0)), None);
+        assert_eq!(
+            tap(Event::Release(49)),
+            Some((Keyboard::A, Modifiers::NONE))
+        );
+        assert_eq!(
+            tap(Event::Pressed(50)),
+            Some((Keyboard::A, Modifiers::LSFT))
+        );
+        assert_eq!(tap(Event::Release(50)), None);
+    }
+}
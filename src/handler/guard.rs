@@ -0,0 +1,170 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::event::Event;
+use crate::handler::{Function, Handle, Tap};
+use crate::state::State;
+
+// Fires `function` only once the key has been held for `thold`, or on a
+// second press within `window` of releasing the guard without firing it.
+// While held and not yet fired, `feedback` is sent every tick, e.g. a custom
+// report lighting a LED.
+pub struct Guard {
+    thold: usize,
+    function: Function,
+    feedback: Option<Function>,
+    window: usize,
+    confirm: Tap,
+    // Tick of the last press, and of the release arming the guard.
+    pressed: AtomicUsize,
+    released: AtomicUsize,
+    armed: AtomicBool,
+}
+
+impl Guard {
+    pub const fn new(
+        thold: usize,
+        window: usize,
+        function: Function,
+        feedback: Option<Function>,
+    ) -> Guard {
+        Guard {
+            thold,
+            function,
+            feedback,
+            window,
+            confirm: Tap::new(function),
+            pressed: AtomicUsize::new(0),
+            released: AtomicUsize::new(0),
+            armed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Handle for Guard {
+    fn handle(&self, event: &Event, _state: &State) -> Option<Function> {
+        match event {
+            Event::Pressed(i) if *i == self.thold => Some(self.function),
+            Event::Press(_) => self.feedback,
+            Event::Pressed(i) if *i < self.thold => self.feedback,
+            Event::Release(i) if *i < self.thold => {
+                let pressed = self.pressed.load(Ordering::Relaxed);
+                self.released
+                    .store(pressed.wrapping_add(*i + 1), Ordering::Relaxed);
+                self.armed.store(true, Ordering::Relaxed);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn resolve(&'static self, event: &Event, state: &State) -> Option<&'static dyn Handle> {
+        let now = state.ticks();
+        let armed = self.armed.swap(false, Ordering::Relaxed);
+        let released = self.released.load(Ordering::Relaxed);
+        match event {
+            Event::Press(_) if armed && now.wrapping_sub(released) < self.window => {
+                Some(&self.confirm)
+            }
+            _ => {
+                self.pressed.store(now, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+// A guard keeps state, so each use of `gd!` declares a static of its own
+// and stands for it, which `keys!` can borrow.
+#[macro_export]
+macro_rules! gd {
+    ($function:expr) => {
+        $crate::gd!(1000, 300, $function)
+    };
+    ($thold:literal, $window:literal, $function:expr) => {
+        $crate::gd!(@static $thold, $window, $function, None)
+    };
+    ($thold:literal, $window:literal, $function:expr, $feedback:expr) => {
+        $crate::gd!(@static $thold, $window, $function, Some($feedback))
+    };
+    (@static $thold:literal, $window:literal, $function:expr, $feedback:expr) => {
+        *{
+            static GUARD: $crate::handler::guard::Guard =
+                $crate::handler::guard::Guard::new($thold, $window, $function, $feedback);
+            &GUARD
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::Event;
+    use crate::function::Function;
+    use crate::handler::guard::Guard;
+    use crate::handler::Handle;
+    use crate::report::{Desktop, Report};
+    use crate::state::State;
+    use crate::{ct, dk, keys};
+
+    static PWR: &Guard = &gd!(100, 20, dk!(SystemPowerDown), ct!(1));
+    static KEYS: [[&dyn Handle; 2]; 1] = keys!([
+        gd!(100, 20, dk!(SystemSleep)), gd!(100, 20, dk!(SystemSleep));
+    ]);
+
+    fn fired(handler: &dyn Handle, event: Event) -> Option<Report> {
+        match handler.handle(&event, &State::new()) {
+            Some(Function::Report(report)) => Some(report),
+            _ => None,
+        }
+    }
+
+    fn wait(state: &mut State, ticks: usize) {
+        (0..ticks).for_each(|_| state.tick());
+    }
+
+    #[test]
+    fn test_gd() {
+        let mut state = State::new();
+        let power = Some(Report::Desktop(Desktop::SystemPowerDown));
+
+        // Long hold
+        assert!(PWR.resolve(&Event::Press(50), &state).is_none());
+        assert_eq!(fired(PWR, Event::Press(50)), Some(Report::Custom(1)));
+        assert_eq!(fired(PWR, Event::Pressed(99)), Some(Report::Custom(1)));
+        assert_eq!(fired(PWR, Event::Pressed(100)), power);
+        assert_eq!(fired(PWR, Event::Pressed(101)), None);
+        assert_eq!(fired(PWR, Event::Release(101)), None);
+
+        // A quick press after another key at this position
+        wait(&mut state, 200);
+        assert!(PWR.resolve(&Event::Press(5), &state).is_none());
+        wait(&mut state, 10);
+        assert_eq!(fired(PWR, Event::Release(9)), None);
+
+        // Double press
+        wait(&mut state, 10);
+        let confirm = PWR.resolve(&Event::Press(10), &state).unwrap();
+        assert_eq!(fired(confirm, Event::Press(10)), power);
+        assert_eq!(fired(confirm, Event::Pressed(100)), None);
+
+        // Too late
+        assert!(PWR.resolve(&Event::Press(50), &state).is_none());
+        wait(&mut state, 10);
+        fired(PWR, Event::Release(9));
+        wait(&mut state, 30);
+        assert!(PWR.resolve(&Event::Press(30), &state).is_none());
+    }
+
+    #[test]
+    fn test_gd_per_key() {
+        let mut state = State::new();
+        let [first, second] = KEYS[0];
+
+        // Releasing the first guard early does not arm the second one.
+        assert!(first.resolve(&Event::Press(50), &state).is_none());
+        wait(&mut state, 10);
+        fired(first, Event::Release(9));
+        wait(&mut state, 5);
+        assert!(second.resolve(&Event::Press(5), &state).is_none());
+        assert!(first.resolve(&Event::Press(5), &state).is_some());
+    }
+}
@@ -1,42 +1,200 @@
 use crate::event::Event;
-use crate::handler::{Function, Handle};
+use crate::handler::{Function, Handle, Hold};
+use crate::mode::Modes;
+use crate::state::State;
 
+// Without a threshold of its own, the key uses the tapping term.
+// In gaming mode, the key holds `tap` like a plain key.
 pub struct HoldTap {
-    thold: usize,
+    thold: Option<usize>,
     hold: Function,
     tap: Function,
+    plain: Hold,
 }
 
 impl HoldTap {
-    pub const fn new(thold: usize, hold: Function, tap: Function) -> HoldTap {
-        HoldTap { thold, hold, tap }
+    pub const fn new(thold: Option<usize>, hold: Function, tap: Function) -> HoldTap {
+        HoldTap {
+            thold,
+            hold,
+            tap,
+            plain: Hold::new(tap),
+        }
     }
 }
 
 impl Handle for HoldTap {
-    fn handle(&self, event: &Event) -> Option<&Function> {
+    fn handle(&self, event: &Event, state: &State) -> Option<Function> {
+        let thold = self
+            .thold
+            .unwrap_or_else(|| state.settings().tapping_term());
         match event {
-            Event::Pressed(i) if *i == self.thold => Some(&self.hold),
-            Event::Release(i) if *i < self.thold => Some(&self.tap),
+            Event::Pressed(i) if *i == thold => Some(self.hold),
+            Event::Release(i) if *i < thold => Some(self.tap),
             _ => None,
         }
     }
+
+    fn resolve(&'static self, _event: &Event, state: &State) -> Option<&'static dyn Handle> {
+        state
+            .modes()
+            .contains(Modes::GAMING)
+            .then_some(&self.plain as &dyn Handle)
+    }
+}
+
+// Like `HoldTap`, with several hold thresholds in increasing order.
+// Each threshold sends its function once, as it is passed, so the key may go
+// e.g. from a layer to a custom report. A layer turned on by a threshold is
+// turned off again when the next threshold is passed, whose function then
+// follows a tick later, or when the key is released. `tap` is sent on
+// release before the first threshold, or held like a plain key in gaming
+// mode.
+pub struct MultiHoldTap<const S: usize> {
+    holds: [(usize, Function); S],
+    tap: Function,
+    plain: Hold,
+}
+
+// Layer turned on by a threshold, to turn off once it is over.
+fn layer(function: Function) -> Option<usize> {
+    match function {
+        Function::Layer(layer) | Function::LayerOn(layer) => Some(layer),
+        _ => None,
+    }
+}
+
+impl<const S: usize> MultiHoldTap<S> {
+    pub const fn new(holds: [(usize, Function); S], tap: Function) -> MultiHoldTap<S> {
+        MultiHoldTap {
+            holds,
+            tap,
+            plain: Hold::new(tap),
+        }
+    }
+
+    // Layer of the threshold before `stage`, turned off as `stage` is passed.
+    fn previous_layer(&self, stage: usize) -> Option<usize> {
+        stage.checked_sub(1).and_then(|k| layer(self.holds[k].1))
+    }
+
+    // Tick at which the function of `stage` is sent.
+    fn fires(&self, stage: usize) -> usize {
+        let (thold, _) = self.holds[stage];
+        match self.previous_layer(stage) {
+            Some(_) => thold + 1,
+            None => thold,
+        }
+    }
+}
+
+impl<const S: usize> Handle for MultiHoldTap<S> {
+    fn handle(&self, event: &Event, _state: &State) -> Option<Function> {
+        match event {
+            Event::Pressed(i) => (0..S).find_map(|k| {
+                let (thold, hold) = self.holds[k];
+                match self.previous_layer(k) {
+                    Some(layer) if *i == thold => Some(Function::LayerOff(layer)),
+                    _ => (*i == self.fires(k)).then_some(hold),
+                }
+            }),
+            Event::Release(i) => match (0..S).rev().find(|k| *i >= self.fires(*k)) {
+                Some(k) => layer(self.holds[k].1).map(Function::LayerOff),
+                None => Some(self.tap),
+            },
+            _ => None,
+        }
+    }
+
+    fn resolve(&'static self, _event: &Event, state: &State) -> Option<&'static dyn Handle> {
+        state
+            .modes()
+            .contains(Modes::GAMING)
+            .then_some(&self.plain as &dyn Handle)
+    }
 }
 
 #[macro_export]
 macro_rules! ht {
     ($thold:literal, $hold:expr, $tap: expr) => {
-        $crate::handler::holdtap::HoldTap::new($thold, $hold, $tap)
+        $crate::handler::holdtap::HoldTap::new(::core::option::Option::Some($thold), $hold, $tap)
+    };
+    ($hold:expr, $tap: expr) => {
+        $crate::handler::holdtap::HoldTap::new(::core::option::Option::None, $hold, $tap)
+    };
+}
+
+#[macro_export]
+macro_rules! mht {
+    ([$($thold:literal => $hold:expr),+ $(,)?], $tap: expr) => {
+        $crate::handler::holdtap::MultiHoldTap::new([$(($thold, $hold)),+], $tap)
     };
 }
 
 #[cfg(test)]
 #[no_implicit_prelude]
 mod test {
-    use crate::{ht, kb};
+    use crate::{ht, kb, mht};
 
     #[test]
     fn test_ht_macros() {
         ht!(50, kb!(F), kb!(J));
+        ht!(kb!(F), kb!(J));
+        mht!([50 => kb!(F), 500 => kb!(G)], kb!(J));
+    }
+}
+
+#[cfg(test)]
+mod test_mht {
+    use crate::event::Event;
+    use crate::function::Function;
+    use crate::handler::holdtap::MultiHoldTap;
+    use crate::handler::Handle;
+    use crate::report::{Keyboard, Report};
+    use crate::state::State;
+    use crate::{ct, kb, lyon, mht};
+
+    static KEY: MultiHoldTap<2> = mht!([50 => kb!(F), 500 => kb!(G)], kb!(J));
+
+    fn key(event: Event) -> Option<Keyboard> {
+        match KEY.handle(&event, &State::new()) {
+            Some(Function::Report(Report::Keyboard(key))) => Some(key),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_mht() {
+        assert_eq!(key(Event::Press(0)), None);
+        assert_eq!(key(Event::Pressed(49)), None);
+        assert_eq!(key(Event::Release(49)), Some(Keyboard::J));
+        assert_eq!(key(Event::Pressed(50)), Some(Keyboard::F));
+        assert_eq!(key(Event::Pressed(499)), None);
+        assert_eq!(key(Event::Pressed(500)), Some(Keyboard::G));
+        assert_eq!(key(Event::Pressed(5000)), None);
+        assert_eq!(key(Event::Release(50)), None);
+    }
+
+    static BOOT: MultiHoldTap<2> = mht!([50 => lyon!(1), 500 => ct!(9)], kb!(Escape));
+
+    #[test]
+    fn test_mht_layer() {
+        let stage = |i| BOOT.handle(&Event::Pressed(i), &State::new());
+        assert!(matches!(stage(50), Some(Function::LayerOn(1))));
+        assert!(stage(51).is_none());
+        assert!(matches!(stage(500), Some(Function::LayerOff(1))));
+        assert!(matches!(
+            stage(501),
+            Some(Function::Report(Report::Custom(9)))
+        ));
+        assert!(stage(502).is_none());
+
+        let release = |i| BOOT.handle(&Event::Release(i), &State::new());
+        assert!(matches!(
+            release(49),
+            Some(Function::Report(Report::Keyboard(Keyboard::Escape)))
+        ));
+        assert!(matches!(release(300), Some(Function::LayerOff(1))));
+        assert!(release(501).is_none());
     }
 }
@@ -0,0 +1,150 @@
+use crate::event::Event;
+use crate::handler::{Function, Handle, Hold};
+use crate::report::{Leds, Modifiers};
+use crate::state::State;
+
+// What a morph key checks when it is pressed.
+pub enum Condition {
+    // Any of the modifiers is held.
+    Modifiers(Modifiers),
+    // The host LED is on, e.g. Num Lock for keypad keys.
+    Led(Leds),
+}
+
+impl Condition {
+    fn holds(&self, state: &State) -> bool {
+        match self {
+            Condition::Modifiers(modifiers) => state.modifiers().intersects(*modifiers),
+            Condition::Led(led) => state.is_lit(*led),
+        }
+    }
+}
+
+// Holds `morph` instead of `normal` if `condition` holds when the key is pressed.
+pub struct Morph {
+    condition: Condition,
+    normal: Hold,
+    morph: Hold,
+}
+
+impl Morph {
+    pub const fn new(condition: Condition, normal: Function, morph: Function) -> Morph {
+        let condition = match condition {
+            Condition::Modifiers(modifiers) => Condition::Modifiers(modifiers.sides()),
+            condition => condition,
+        };
+        Morph {
+            condition,
+            normal: Hold::new(normal),
+            morph: Hold::new(morph),
+        }
+    }
+}
+
+impl Handle for Morph {
+    fn handle(&self, event: &Event, state: &State) -> Option<Function> {
+        self.normal.handle(event, state)
+    }
+
+    fn resolve(&'static self, _event: &Event, state: &State) -> Option<&'static dyn Handle> {
+        match self.condition.holds(state) {
+            true => Some(&self.morph),
+            false => Some(&self.normal),
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! mm {
+    ($($m:ident)|+, $normal:expr, $morph:expr) => {
+        $crate::handler::morph::Morph::new(
+            $crate::handler::morph::Condition::Modifiers(
+                $crate::report::Modifiers::NONE$(.union($crate::report::Modifiers::$m))+,
+            ),
+            $normal,
+            $morph,
+        )
+    };
+}
+
+// Morphs on a host LED, taking the lit function first.
+#[macro_export]
+macro_rules! lm {
+    ($led:ident, $lit:expr, $unlit:expr) => {
+        $crate::handler::morph::Morph::new(
+            $crate::handler::morph::Condition::Led($crate::report::Leds::$led),
+            $unlit,
+            $lit,
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::Event;
+    use crate::function::Function;
+    use crate::handler::morph::Morph;
+    use crate::handler::Handle;
+    use crate::report::{Keyboard, KeyboardLedsReport, Modifiers, Report};
+    use crate::state::State;
+    use crate::{kb, lm, mm};
+
+    static GESC: Morph = mm!(SFT | GUI, kb!(Escape), kb!(Grave));
+    static P1: Morph = lm!(NumLock, kb!(Keypad1), kb!(End));
+
+    fn key(handler: &dyn Handle) -> Option<Keyboard> {
+        match handler.handle(&Event::Press(0), &State::new()) {
+            Some(Function::Report(Report::Keyboard(key))) => Some(key),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_mm() {
+        let mut state = State::new();
+        assert_eq!(
+            key(GESC.resolve(&Event::Press(0), &state).unwrap()),
+            Some(Keyboard::Escape)
+        );
+
+        state.set_modifiers(Modifiers::RSFT);
+        assert_eq!(
+            key(GESC.resolve(&Event::Press(0), &state).unwrap()),
+            Some(Keyboard::Grave)
+        );
+
+        state.set_modifiers(Modifiers::LCTL);
+        assert_eq!(
+            key(GESC.resolve(&Event::Press(0), &state).unwrap()),
+            Some(Keyboard::Escape)
+        );
+    }
+
+    #[test]
+    fn test_lm() {
+        let mut state = State::new();
+        let press = Event::Press(0);
+        assert_eq!(
+            key(P1.resolve(&press, &state).unwrap()),
+            Some(Keyboard::End)
+        );
+
+        state.set_leds(KeyboardLedsReport {
+            num_lock: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            key(P1.resolve(&press, &state).unwrap()),
+            Some(Keyboard::Keypad1)
+        );
+
+        state.set_leds(KeyboardLedsReport {
+            caps_lock: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            key(P1.resolve(&press, &state).unwrap()),
+            Some(Keyboard::End)
+        );
+    }
+}
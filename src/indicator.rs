@@ -0,0 +1,8 @@
+use crate::report::KeyboardLedsReport;
+use crate::state::State;
+
+// Board specific indicators, e.g. LEDs mirroring the host lock keys.
+// Called whenever the host sends new LED states.
+pub trait Indicator: Sync {
+    fn indicate(&self, leds: KeyboardLedsReport, state: &State);
+}
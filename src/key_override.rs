@@ -0,0 +1,121 @@
+use crate::keymap::Reports;
+use crate::report::{Keyboard, Modifiers, Report};
+
+// Sends `replacement` with `modifiers` instead of `key` while the `trigger`
+// modifiers are held. The held trigger modifiers are left out of the report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyOverride {
+    trigger: Modifiers,
+    key: Keyboard,
+    replacement: Keyboard,
+    modifiers: Modifiers,
+}
+
+impl KeyOverride {
+    pub const fn new(
+        trigger: Modifiers,
+        key: Keyboard,
+        replacement: Keyboard,
+        modifiers: Modifiers,
+    ) -> KeyOverride {
+        KeyOverride {
+            trigger,
+            key,
+            replacement,
+            modifiers,
+        }
+    }
+
+    pub fn matches(&self, key: Keyboard, held: Modifiers) -> bool {
+        self.key == key && held.matches(self.trigger)
+    }
+}
+
+pub fn apply(overrides: &[KeyOverride], reports: &mut Reports) {
+    let held = Modifiers::from_reports(reports);
+    let mut suppressed = Modifiers::NONE;
+    let mut added = Modifiers::NONE;
+
+    for report in reports.iter_mut() {
+        let Report::Keyboard(key) = report else {
+            continue;
+        };
+        let matching = overrides.iter().find(|o| o.matches(*key, held));
+        if let Some(o) = matching {
+            *key = o.replacement;
+            suppressed = suppressed.union(held.intersection(o.trigger));
+            added = added.union(o.modifiers);
+        }
+    }
+
+    reports.retain(|report| match report {
+        Report::Keyboard(key) => !Modifiers::from_key(*key).intersects(suppressed),
+        _ => true,
+    });
+    for key in added.keys() {
+        if !reports.contains(&Report::Keyboard(key)) {
+            reports.push(Report::Keyboard(key)).unwrap();
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! ko {
+    ($trigger:tt, $key:tt, $replacement:tt) => {
+        $crate::ko!($trigger, $key, NONE, $replacement)
+    };
+    ($trigger:tt, $key:tt, $modifiers:tt, $replacement:tt) => {
+        $crate::key_override::KeyOverride::new(
+            $crate::report::Modifiers::$trigger,
+            $crate::report::Keyboard::$key,
+            $crate::report::Keyboard::$replacement,
+            $crate::report::Modifiers::$modifiers,
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static OVERRIDES: [KeyOverride; 3] = [
+        ko!(SFT, DeleteBackspace, DeleteForward),
+        ko!(SFT, Comma, LSFT, Keyboard9),
+        ko!(LSFT, Dot, Keyboard0),
+    ];
+
+    fn reports(keys: &[Keyboard]) -> Reports {
+        keys.iter().map(|key| Report::Keyboard(*key)).collect()
+    }
+
+    #[test]
+    fn test_ko() {
+        let mut output = reports(&[Keyboard::RightShift, Keyboard::DeleteBackspace]);
+        apply(&OVERRIDES, &mut output);
+        assert_eq!(output, reports(&[Keyboard::DeleteForward]));
+
+        let mut output = reports(&[Keyboard::LeftShift, Keyboard::Comma]);
+        apply(&OVERRIDES, &mut output);
+        assert_eq!(output, reports(&[Keyboard::Keyboard9, Keyboard::LeftShift]));
+
+        // Only the trigger side is left out
+        let mut output = reports(&[Keyboard::LeftShift, Keyboard::RightShift, Keyboard::Dot]);
+        apply(&OVERRIDES, &mut output);
+        assert_eq!(
+            output,
+            reports(&[Keyboard::RightShift, Keyboard::Keyboard0])
+        );
+
+        // A left-side trigger does not match the right side
+        let mut output = reports(&[Keyboard::RightShift, Keyboard::Dot]);
+        apply(&OVERRIDES, &mut output);
+        assert_eq!(output, reports(&[Keyboard::RightShift, Keyboard::Dot]));
+
+        let mut output = reports(&[Keyboard::LeftControl, Keyboard::DeleteBackspace]);
+        apply(&OVERRIDES, &mut output);
+        assert_eq!(
+            output,
+            reports(&[Keyboard::LeftControl, Keyboard::DeleteBackspace])
+        );
+    }
+}
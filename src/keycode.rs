@@ -0,0 +1,233 @@
+use crate::event::Event;
+use crate::function::Function;
+use crate::handler::{Handle, Hold, OnOff, Tap};
+use crate::mouse::MouseKey;
+use crate::report::{Consumer, Desktop, Keyboard, Modifiers, Report};
+use crate::state::State;
+
+// 16-bit keycodes as used by QMK and VIA.
+pub const NO: u16 = 0x0000;
+pub const TRANSPARENT: u16 = 0x0001;
+
+// Behavior of a keycode, following the handlers of the same name.
+#[derive(Clone, Copy)]
+pub enum Key {
+    None,
+    // Falls through to the next active layer below.
+    Transparent,
+    Hold(Function),
+    Tap(Function),
+    OnOff(Function, Function),
+    // Sends the function when tapped, holds the layer past the tapping term.
+    LayerTap(usize, Function),
+    // Sends the function when tapped, holds the modifier past the tapping term.
+    ModTap(Keyboard, Function),
+}
+
+impl Key {
+    pub fn handle(&self, event: &Event, state: &State) -> Option<Function> {
+        let term = state.settings().tapping_term();
+        match *self {
+            Key::None | Key::Transparent => None,
+            Key::Hold(f) => Hold::new(f).handle(event, state),
+            Key::Tap(f) => Tap::new(f).handle(event, state),
+            Key::OnOff(f0, f1) => OnOff::new(f0, f1).handle(event, state),
+            Key::LayerTap(layer, tap) => match event {
+                Event::Pressed(i) if *i == term => Some(Function::LayerOn(layer)),
+                Event::Release(i) if *i >= term => Some(Function::LayerOff(layer)),
+                Event::Release(_) => Some(tap),
+                _ => None,
+            },
+            Key::ModTap(modifier, tap) => match event {
+                Event::Pressed(i) if *i >= term => {
+                    Some(Function::Report(Report::Keyboard(modifier)))
+                }
+                Event::Release(i) if *i < term => Some(tap),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn keyboard(code: u8) -> Function {
+    Function::Report(Report::Keyboard(Keyboard::from(code)))
+}
+
+// Modifier bits 8 to 12 of a keycode: Ctrl, Shift, Alt, GUI and right side.
+fn modifiers(code: u16) -> Modifiers {
+    let bits = ((code >> 8) & 0x0F) as u8;
+    match code & 0x1000 != 0 {
+        true => Modifiers::from_bits(bits << 4),
+        false => Modifiers::from_bits(bits),
+    }
+}
+
+fn special(code: u8) -> Key {
+    let desktop = |d| Key::Tap(Function::Report(Report::Desktop(d)));
+    let consumer = |c| Key::Hold(Function::Report(Report::Consumer(c)));
+    let mouse = |m| Key::Hold(Function::Mouse(m));
+    match code {
+        0xA5 => desktop(Desktop::SystemPowerDown),
+        0xA6 => desktop(Desktop::SystemSleep),
+        0xA7 => desktop(Desktop::SystemWakeUp),
+        0xA8 => consumer(Consumer::Mute),
+        0xA9 => consumer(Consumer::VolumeIncrement),
+        0xAA => consumer(Consumer::VolumeDecrement),
+        0xAB => consumer(Consumer::TrackingIncrement),
+        0xAC => consumer(Consumer::TrackingDecrement),
+        0xAD => consumer(Consumer::Stop),
+        0xAE => consumer(Consumer::PlayPause),
+        0xCD => mouse(MouseKey::Up),
+        0xCE => mouse(MouseKey::Down),
+        0xCF => mouse(MouseKey::Left),
+        0xD0 => mouse(MouseKey::Right),
+        0xD1 => mouse(MouseKey::Button1),
+        0xD2 => mouse(MouseKey::Button2),
+        0xD3 => mouse(MouseKey::Button3),
+        0xD4 => mouse(MouseKey::Button4),
+        0xD5 => mouse(MouseKey::Button5),
+        0xD9 => mouse(MouseKey::WheelUp),
+        0xDA => mouse(MouseKey::WheelDown),
+        0xDB => mouse(MouseKey::WheelLeft),
+        0xDC => mouse(MouseKey::WheelRight),
+        0xDD => mouse(MouseKey::Accel0),
+        0xDE => mouse(MouseKey::Accel1),
+        0xDF => mouse(MouseKey::Accel2),
+        _ => Key::None,
+    }
+}
+
+// Layer argument of a layer keycode.
+pub fn layer(code: u16) -> Option<usize> {
+    match code {
+        0x4000..=0x4FFF => Some(((code >> 8) & 0x0F) as usize),
+        0x5200..=0x527F => Some((code & 0x1F) as usize),
+        _ => None,
+    }
+}
+
+// Unsupported keycodes do nothing, as do layer keycodes past `layers`.
+pub fn decode(code: u16, layers: usize) -> Key {
+    if layer(code).is_some_and(|layer| layer >= layers) {
+        return Key::None;
+    }
+    let low = code as u8;
+    let layer = (code & 0x1F) as usize;
+    match code {
+        NO => Key::None,
+        TRANSPARENT => Key::Transparent,
+        0x0004..=0x00A4 | 0x00E0..=0x00E7 => Key::Hold(keyboard(low)),
+        0x00A5..=0x00DF => special(low),
+        // Basic key with modifiers
+        0x0100..=0x1FFF => match low {
+            0x04..=0xA4 | 0xE0..=0xE7 => {
+                Key::Tap(Function::Tap(Keyboard::from(low), modifiers(code)))
+            }
+            _ => Key::None,
+        },
+        // Mod-Tap
+        0x2000..=0x3FFF => match modifiers(code).keys().next() {
+            Some(modifier) => Key::ModTap(modifier, keyboard(low)),
+            None => Key::None,
+        },
+        // Layer-Tap
+        0x4000..=0x4FFF => Key::LayerTap(((code >> 8) & 0x0F) as usize, keyboard(low)),
+        // TO(layer), layer-mods (LM) at 0x5000..=0x51FF are not supported
+        0x5200..=0x521F => Key::Tap(Function::Layer(layer)),
+        // DF(layer)
+        0x5240..=0x525F => Key::Tap(Function::DefaultLayer(layer)),
+        // MO(layer)
+        0x5220..=0x523F => Key::OnOff(Function::LayerOn(layer), Function::LayerOff(layer)),
+        // TG(layer)
+        0x5260..=0x527F => Key::Tap(Function::LayerToggle(layer)),
+        _ => Key::None,
+    }
+}
+
+// Whether a keycode is supported in a keymap of `layers`, `NO` being the
+// only one that does nothing.
+pub fn is_valid(code: u16, layers: usize) -> bool {
+    code == NO || !matches!(decode(code, layers), Key::None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sequence::{Frame, Sequence};
+
+    fn report(code: u16, event: Event) -> Option<Report> {
+        match decode(code, 4).handle(&event, &State::new()) {
+            Some(Function::Report(report)) => Some(report),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let a = Some(Report::Keyboard(Keyboard::A));
+        assert_eq!(report(0x0004, Event::Pressed(3)), a);
+        assert_eq!(
+            report(0x00A8, Event::Press(0)),
+            Some(Report::Consumer(Consumer::Mute))
+        );
+        assert!(matches!(decode(TRANSPARENT, 4), Key::Transparent));
+        assert!(matches!(decode(0x7FFF, 4), Key::None));
+        assert!(is_valid(NO, 4) && !is_valid(0x7FFF, 4) && !is_valid(0x00B0, 4));
+
+        // LCTL(KC_A), RSFT(KC_A)
+        assert!(matches!(
+            decode(0x0104, 4).handle(&Event::Press(0), &State::new()),
+            Some(Function::Tap(Keyboard::A, Modifiers::LCTL))
+        ));
+        assert!(matches!(
+            decode(0x1204, 4).handle(&Event::Press(0), &State::new()),
+            Some(Function::Tap(Keyboard::A, Modifiers::RSFT))
+        ));
+
+        // HYPR(KC_A) taps A with all four modifiers
+        let Some(Function::Tap(key, modifiers)) =
+            decode(0x0F04, 4).handle(&Event::Press(0), &State::new())
+        else {
+            panic!("HYPR(KC_A) is a tap");
+        };
+        let held: Frame = modifiers.keys().collect();
+        let mut sequence = Sequence::new(1);
+        sequence.tap(&held, &[key]);
+        assert_eq!(sequence.play().unwrap().len(), 5);
+
+        // MT(MOD_LSFT, KC_A)
+        assert_eq!(report(0x2204, Event::Release(10)), a);
+        let shift = Some(Report::Keyboard(Keyboard::LeftShift));
+        assert_eq!(report(0x2204, Event::Pressed(200)), shift);
+
+        // LT(2, KC_A), MO(3)
+        assert_eq!(report(0x4204, Event::Release(10)), a);
+        assert!(matches!(
+            decode(0x4204, 4).handle(&Event::Pressed(200), &State::new()),
+            Some(Function::LayerOn(2))
+        ));
+        assert!(matches!(
+            decode(0x5223, 4).handle(&Event::Release(5), &State::new()),
+            Some(Function::LayerOff(3))
+        ));
+
+        // DF(1)
+        assert!(matches!(
+            decode(0x5241, 4).handle(&Event::Press(0), &State::new()),
+            Some(Function::DefaultLayer(1))
+        ));
+
+        // TO(1), LM(1, MOD_LCTL)
+        assert!(matches!(
+            decode(0x5201, 4).handle(&Event::Press(0), &State::new()),
+            Some(Function::Layer(1))
+        ));
+        assert!(is_valid(0x5201, 4) && !is_valid(0x5021, 4));
+        assert!(!is_valid(0x5203, 3));
+
+        // MO(3), LT(3, KC_A) past the layers
+        assert!(matches!(decode(0x5223, 3), Key::None));
+        assert!(matches!(decode(0x4304, 3), Key::None));
+        assert!(is_valid(0x5223, 4) && !is_valid(0x5223, 3));
+    }
+}
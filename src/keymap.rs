@@ -1,50 +1,146 @@
 use heapless::spsc::Producer;
+use heapless::Vec;
 use itertools::izip;
 
+use crate::access::{Filters, StickyModifiers};
+use crate::action::Action;
+use crate::command::{self, Statistics, Transport};
 use crate::debouncer::{Debounce, Debouncer};
 use crate::event::Event;
 use crate::function::Function;
+use crate::gamepad;
 use crate::handler::Handle;
+use crate::indicator::Indicator;
+use crate::key_override::{self, KeyOverride};
+use crate::layer::{self, Condition};
+use crate::lock::{KeyLock, Lock};
+use crate::mode::Modes;
+use crate::mouse::{self, MouseKeys};
+use crate::pointing::{Pointing, Sensor};
 use crate::processor::Process;
-use crate::report::Report;
+use crate::recorder::{Item, Overflow, Recorder};
+use crate::repeat::Repeater;
+use crate::report::{Keyboard, KeyboardLedsReport, Modifiers, Report};
+use crate::sequence::{Frame, Sequence};
+use crate::settings::{self, Settings};
+use crate::state::{State, MAX_LAYERS};
+use crate::unicode;
 
 const MAX_REPORTS: usize = 128;
-const DT: usize = 5;
+const HOLD: usize = 10;
+const MACRO_SLOTS: usize = 4;
+const MACRO_ITEMS: usize = 128;
 
-trait Keymap<const N: usize, const L: usize> {
+pub type Reports = Vec<Report, MAX_REPORTS>;
+
+pub trait Keymap<const N: usize, const L: usize> {
     type DB: Debounce;
     fn tick(&mut self, switches: &[bool; N]);
 }
 
 pub struct BasicKeymap<const N: usize, const L: usize> {
     events: [Event; N],
-    layer: usize,
-    debouncers: [Debouncer<DT>; N],
+    state: State,
+    statistics: Statistics,
+    debouncers: [Debouncer; N],
+    filters: Filters<N>,
     handlers: [Option<&'static dyn Handle>; N],
+    lock: KeyLock<N>,
+    sticky: StickyModifiers,
+    mouse: MouseKeys,
+    pointing: Pointing,
+    motion: Reports,
+    sequence: Sequence,
+    recorder: Recorder<MACRO_SLOTS, MACRO_ITEMS>,
+    repeater: Repeater,
+    overrides: &'static [KeyOverride],
+    blocked: &'static [Keyboard],
+    conditions: &'static [Condition],
+    action: Option<&'static dyn Action>,
+    indicator: Option<&'static dyn Indicator>,
     processors: &'static [&'static dyn Process<N, L>],
     reporter: Producer<'static, Report, MAX_REPORTS>,
 }
 
 impl<const N: usize, const L: usize> Keymap<N, L> for BasicKeymap<N, L> {
-    type DB = Debouncer<DT>;
+    type DB = Debouncer;
 
     fn tick(&mut self, switches: &[bool; N]) {
+        let dt = self.state.settings().debounce();
         for (event, debouncer, switch) in izip!(&mut self.events, &mut self.debouncers, switches) {
+            debouncer.set_dt(dt);
             *event = debouncer.debounce(*switch);
         }
+        self.filters.filter(&mut self.events, self.state.modes());
+        self.lock.update(&mut self.events);
+        self.statistics.count(&self.events);
+        self.state.tick();
 
         // Process all events.
         self.processors
             .iter()
-            .for_each(|handler| handler.process(&mut self.handlers, &self.events, self.layer));
+            .for_each(|handler| handler.process(&mut self.handlers, &self.events, &self.state));
+        if self.events.iter().any(|e| matches!(e, Event::Press(_))) {
+            self.state.swap_used();
+        }
 
         // Handle individual events.
-        for (handler, event) in izip!(&mut self.handlers, &self.events) {
+        let mut reports = Reports::new();
+        for (i, (handler, event)) in izip!(&mut self.handlers, &self.events).enumerate() {
+            if matches!(event, Event::Press(_)) {
+                if let Some(resolved) = handler.and_then(|h| h.resolve(event, &self.state)) {
+                    *handler = Some(resolved);
+                }
+            }
             if let Some(handler) = handler {
-                if let Some(function) = handler.handle(event) {
-                    match function {
-                        Function::Report(report) => self.reporter.enqueue(*report).unwrap(),
-                        Function::Layer(layer) => self.layer = *layer,
+                let function = handler.handle(event, &self.state);
+                if matches!(event, Event::Press(_))
+                    && self.lock.is_armed()
+                    && !matches!(function, Some(Function::Lock(Lock::Key)))
+                {
+                    self.lock.lock(i);
+                }
+                if let Some(function) = function {
+                    match (function, self.action) {
+                        (Function::Report(Report::Custom(id)), Some(action)) => {
+                            action.act(id, &mut self.state, &mut reports)
+                        }
+                        (Function::Report(report), _) => reports.push(report).unwrap(),
+                        (Function::Tap(key, modifiers), _) => {
+                            if !self.sequence.is_playing() {
+                                self.sequence = Sequence::new(HOLD);
+                            }
+                            if self.sequence.fits(2) {
+                                let held: Frame = modifiers.keys().collect();
+                                self.sequence.tap(&held, &[key]);
+                            }
+                        }
+                        (Function::Layer(layer), _) => self.state.set_layer(layer),
+                        (Function::LayerOn(layer), _) => self.state.layer_on(layer),
+                        (Function::LayerOff(layer), _) => self.state.layer_off(layer),
+                        (Function::LayerToggle(layer), _) => self.state.layer_toggle(layer),
+                        (Function::DefaultLayer(layer), _) => self.state.set_default_layer(layer),
+                        (Function::Lock(Lock::Layer), _) => {
+                            self.state.lock_layer(self.state.layer())
+                        }
+                        (Function::Lock(Lock::Key), _) => self.lock.arm(),
+                        (Function::Toggle(modes), _) => self.state.toggle(modes),
+                        (Function::Swap(swap), _) => self.state.swap(swap),
+                        (Function::Mouse(key), _) => self.mouse.press(key, event),
+                        (Function::Unicode(c), _) if !self.sequence.is_playing() => {
+                            self.sequence = unicode::sequence(self.state.unicode(), c, HOLD)
+                        }
+                        (Function::Unicode(_), _) => {}
+                        (Function::UnicodeMode(mode), _) => self.state.set_unicode(mode),
+                        (Function::Macro(command), _) => self.recorder.run(command),
+                        (Function::Repeat(repeat), _) => self.repeater.press(repeat),
+                        (Function::Tune(tune), _) => {
+                            let term = self.state.settings_mut().tune(tune);
+                            if let Some(term) = term.filter(|_| !self.sequence.is_playing()) {
+                                self.sequence = Sequence::new(HOLD);
+                                settings::print(&mut self.sequence, term);
+                            }
+                        }
                     }
                 }
             }
@@ -52,6 +148,57 @@ impl<const N: usize, const L: usize> Keymap<N, L> for BasicKeymap<N, L> {
                 *handler = None;
             }
         }
+        if self
+            .events
+            .iter()
+            .all(|e| matches!(e, Event::Release(_) | Event::Released(_)))
+        {
+            self.state.settings_mut().settle();
+        }
+        layer::apply(self.conditions, &mut self.state);
+
+        if let Some(frame) = self.sequence.play() {
+            reports.extend(frame.iter().map(|key| Report::Keyboard(*key)));
+        }
+
+        self.pointing.set_scroll(self.mouse.scroll());
+        if let Some(report) = self.mouse.report() {
+            self.pointing.keep_alive();
+            reports.push(Report::Mouse(report)).unwrap();
+        }
+        reports.extend(self.motion.iter().copied());
+        self.motion.clear();
+
+        if let Some(items) = self.recorder.play() {
+            for item in items {
+                if let Item::Report(report) = item {
+                    reports.push(*report).unwrap();
+                }
+            }
+        }
+
+        gamepad::merge(&mut reports);
+        mouse::merge(&mut reports);
+        if self.state.modes().contains(Modes::GAMING) {
+            reports.retain(|report| match report {
+                Report::Keyboard(key) => !self.blocked.contains(key),
+                _ => true,
+            });
+        }
+        match self.state.modes().contains(Modes::STICKY_MODIFIERS) {
+            true => self.sticky.apply(&mut reports),
+            false => self.sticky = StickyModifiers::new(),
+        }
+        self.state.set_modifiers(Modifiers::from_reports(&reports));
+        key_override::apply(self.overrides, &mut reports);
+        self.repeater.update(&mut reports);
+
+        // Playback is recorded like any other report, so a macro played while
+        // recording another one becomes part of it.
+        self.recorder.record(&reports);
+        for report in reports {
+            self.reporter.enqueue(report).unwrap();
+        }
     }
 }
 
@@ -60,27 +207,145 @@ impl<const N: usize, const L: usize> BasicKeymap<N, L> {
         processors: &'static [&'static dyn Process<N, L>],
         reporter: Producer<'static, Report, MAX_REPORTS>,
     ) -> BasicKeymap<N, L> {
+        assert!(L <= MAX_LAYERS);
         BasicKeymap {
             events: [Event::default(); N],
             handlers: [None; N],
-            layer: 0,
-            debouncers: [Debouncer::<DT>::new(); N],
+            lock: KeyLock::new(),
+            sticky: StickyModifiers::new(),
+            state: State::new(),
+            statistics: Statistics::default(),
+            debouncers: [Debouncer::new(Settings::default().debounce()); N],
+            filters: Filters::default(),
+            mouse: MouseKeys::default(),
+            pointing: Pointing::default(),
+            motion: Reports::new(),
+            sequence: Sequence::default(),
+            recorder: Recorder::default(),
+            repeater: Repeater::default(),
+            overrides: &[],
+            blocked: &[],
+            conditions: &[],
+            action: None,
+            indicator: None,
             processors,
             reporter,
         }
     }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    // For restoring saved settings at boot.
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    pub fn set_mouse_keys(&mut self, mouse: MouseKeys) {
+        self.mouse = mouse;
+    }
+
+    pub fn set_filters(&mut self, filters: Filters<N>) {
+        self.filters = filters;
+    }
+
+    pub fn set_action(&mut self, action: &'static dyn Action) {
+        self.action = Some(action);
+    }
+
+    pub fn set_indicator(&mut self, indicator: &'static dyn Indicator) {
+        self.indicator = Some(indicator);
+    }
+
+    // Feeds the LED output report received from the host.
+    pub fn set_leds(&mut self, leds: KeyboardLedsReport) {
+        if leds == self.state.leds() {
+            return;
+        }
+        self.state.set_leds(leds);
+        if let Some(indicator) = self.indicator {
+            indicator.indicate(leds, &self.state);
+        }
+    }
+
+    pub fn set_overrides(&mut self, overrides: &'static [KeyOverride]) {
+        self.overrides = overrides;
+    }
+
+    // Keys left out while in gaming mode.
+    pub fn set_blocked(&mut self, blocked: &'static [Keyboard]) {
+        self.blocked = blocked;
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        *self.state.settings_mut() = settings;
+    }
+
+    // Keys excluded from auto-shift.
+    pub fn set_unshifted(&mut self, keys: &'static [Keyboard]) {
+        self.state.set_unshifted(keys);
+    }
+
+    pub fn set_conditions(&mut self, conditions: &'static [Condition]) {
+        self.conditions = conditions;
+    }
+
+    // Counterparts sent by the alternate repeat key.
+    pub fn set_alternates(&mut self, alternates: &'static [KeyOverride]) {
+        self.repeater.set_alternates(alternates);
+    }
+
+    pub fn set_macro_overflow(&mut self, overflow: Overflow) {
+        self.recorder.set_overflow(overflow);
+    }
+
+    pub fn set_pointing(&mut self, pointing: Pointing) {
+        self.pointing = pointing;
+    }
+
+    // Polls the pointing device, once per tick. The motion is sent with the
+    // reports of the next tick.
+    pub fn point(&mut self, sensor: &mut impl Sensor) {
+        let (report, function) = self.pointing.update(sensor.motion());
+        if let Some(report) = report {
+            self.motion.push(Report::Mouse(report)).unwrap();
+            mouse::merge(&mut self.motion);
+        }
+        match function {
+            Some(Function::LayerOn(layer)) => self.state.layer_on(layer),
+            Some(Function::LayerOff(layer)) => self.state.layer_off(layer),
+            _ => return,
+        }
+        layer::apply(self.conditions, &mut self.state);
+    }
+
+    // Answers one pending request from the host, if any.
+    pub fn serve(&mut self, transport: &mut impl Transport) {
+        if let Some(request) = transport.receive() {
+            let response =
+                command::respond::<L>(&request, &mut self.state, &self.events, &self.statistics);
+            transport.send(&response);
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use heapless::spsc::{Consumer, Queue};
 
+    use crate::action::Action;
     use crate::debouncer::{Debounce, Debouncer};
     use crate::handler::Handle;
-    use crate::keymap::{BasicKeymap, Keymap};
+    use crate::keymap::{BasicKeymap, Keymap, Reports};
+    use crate::layer::Condition;
     use crate::processor::chord::Chord;
+    use crate::processor::socd::Socd;
+    use crate::processor::swap::SwapHands;
     use crate::processor::{KeyProcessor, Process};
-    use crate::report::{Keyboard, Report};
+    use crate::report::{Keyboard, Report, WheelMouseReport};
+    use crate::settings::Settings;
+    use crate::state::State;
     use crate::*;
 
     const MAX_REPORTS: usize = 128;
@@ -175,7 +440,7 @@ mod test {
 
     #[test]
     fn test() {
-        let (producer, consumer) = unsafe { Q.split() };
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(Q)).split() };
         let keymap: BasicKeymap<N, L> = BasicKeymap::new(&HANDLERS, producer);
 
         let mut tester = Tester::new(keymap, consumer);
@@ -194,4 +459,380 @@ mod test {
         tester.test(&[1, 2, 1, 2, 1], &[0, 6, 0, 6, 5], &[r!(A)]); // chording 1
         tester.test(&[4, 2, 4, 2, 0], &[0, 6, 0, 6, 5], &[r!(B)]); // chording 2
     }
+
+    static mut QA: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_ACTION: [[&dyn Handle; 2]; 2] = keys!(
+        [
+            cttp!(1), cttp!(2);
+        ],
+        [
+            kc!(A), kc!(B);
+        ]
+    );
+    static KH_ACTION: KeyProcessor<2, 2> = KeyProcessor::new(KEYS_ACTION);
+    static HANDLERS_ACTION: [&'static dyn Process<2, 2>; 1] = [&KH_ACTION];
+
+    struct Board;
+
+    impl Action for Board {
+        fn act(&self, id: u8, state: &mut State, reports: &mut Reports) {
+            match id {
+                1 => state.set_layer(1),
+                _ => reports.push(r!(Z)).unwrap(),
+            }
+        }
+    }
+
+    static BOARD: Board = Board;
+
+    #[test]
+    fn test_action() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QA)).split() };
+        let mut keymap: BasicKeymap<2, 2> = BasicKeymap::new(&HANDLERS_ACTION, producer);
+        keymap.set_action(&BOARD);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[1], &[5], &[r!(Z)]); // custom report
+        tester.test(&[0, 0, 1], &[6, 6, 5], &[r!(B)]); // layer 0 -> 1
+    }
+
+    static mut QL: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_LAYERS: [[&dyn Handle; 3]; 4] = keys!(
+        [
+            lymo!(1), lymo!(2), kc!(A);
+        ],
+        [
+            lymo!(1), lymo!(2), kc!(B);
+        ],
+        [
+            lymo!(1), lymo!(2), kc!(C);
+        ],
+        [
+            lymo!(1), lymo!(2), kc!(D);
+        ]
+    );
+    static KH_LAYERS: KeyProcessor<3, 4> = KeyProcessor::new(KEYS_LAYERS);
+    static HANDLERS_LAYERS: [&'static dyn Process<3, 4>; 1] = [&KH_LAYERS];
+    static CONDITIONS: [Condition; 1] = [tri!(1, 2, 3)];
+
+    #[test]
+    fn test_layers() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QL)).split() };
+        let mut keymap: BasicKeymap<3, 4> = BasicKeymap::new(&HANDLERS_LAYERS, producer);
+        keymap.set_conditions(&CONDITIONS);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0, 2], &[6, 5], &[r!(B)]); // layer 1
+        tester.test(&[1, 2], &[6, 5], &[r!(C)]); // layer 2
+        tester.test(&[0, 1, 2], &[6, 6, 5], &[r!(D)]); // layers 1 + 2 -> 3
+        tester.test(&[0, 1, 0, 2], &[6, 6, 6, 5], &[r!(C)]); // layer 3 dropped with 1
+        tester.test(&[2], &[5], &[r!(A)]); // back to layer 0
+    }
+
+    static mut QK: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_LOCK: [[&dyn Handle; 4]; 2] = keys!(
+        [
+            lymo!(1), kc!(QK_LOCK), kc!(A), kc!(C);
+        ],
+        [
+            lymo!(1), kc!(QK_LLCK), kc!(B), kc!(C);
+        ]
+    );
+    static KH_LOCK: KeyProcessor<4, 2> = KeyProcessor::new(KEYS_LOCK);
+    static HANDLERS_LOCK: [&'static dyn Process<4, 2>; 1] = [&KH_LOCK];
+
+    #[test]
+    fn test_lock() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QK)).split() };
+        let keymap: BasicKeymap<4, 2> = BasicKeymap::new(&HANDLERS_LOCK, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0, 1, 1, 0, 2], &[6, 6, 6, 6, 5], &[r!(B)]); // lock layer 1
+        tester.test(&[1, 1, 2], &[6, 6, 5], &[r!(A)]); // unlock layer 1
+        tester.test(&[1, 1, 2, 2, 3], &[6, 6, 6, 6, 5], &[r!(A), r!(C)]); // lock A
+        tester.test(&[3], &[5], &[r!(A), r!(C)]); // A still down
+        tester.test(&[2, 2, 3], &[6, 6, 5], &[r!(C)]); // unlock A
+    }
+
+    static mut QS: Queue<Report, MAX_REPORTS> = Queue::new();
+    static mut QU: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_SHIFT: [[&dyn Handle; 3]; 1] = keys!([
+        asft!(50, A), kc!(B), handler::Tap::new(tp!(LCTL | LSFT | LALT | LGUI | RCTL, C));
+    ]);
+    static KH_SHIFT: KeyProcessor<3, 1> = KeyProcessor::new(KEYS_SHIFT);
+    static HANDLERS_SHIFT: [&'static dyn Process<3, 1>; 1] = [&KH_SHIFT];
+
+    #[test]
+    fn test_autoshift() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QS)).split() };
+        let keymap: BasicKeymap<3, 1> = BasicKeymap::new(&HANDLERS_SHIFT, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        let tap = [
+            r!(LeftControl),
+            r!(LeftShift),
+            r!(LeftAlt),
+            r!(LeftGUI),
+            r!(RightControl),
+            r!(C),
+        ];
+        tester.test(&[2], &[5], &tap); // five modifiers
+        tester.test(&[0, 0], &[49, 5], &[r!(A)]); // tap
+        tester.test(&[0], &[55], &[r!(LeftShift), r!(A)]); // hold
+        tester.test(&[0, 0, 1], &[49, 0, 5], &[r!(B), r!(A)]); // tap, then B
+    }
+
+    #[test]
+    fn test_autoshift_excluded() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QU)).split() };
+        let mut keymap: BasicKeymap<3, 1> = BasicKeymap::new(&HANDLERS_SHIFT, producer);
+        keymap.set_unshifted(&[Keyboard::A]);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0], &[55], &[r!(A)]); // held, not shifted
+    }
+
+    static mut QH: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_SWAP: [[&dyn Handle; 5]; 1] = keys!([
+        kc!(A), kc!(B), kc!(SH_OS), kc!(SH_TOGG), kc!(SH_MON);
+    ]);
+    static KH_SWAP: KeyProcessor<5, 1> = KeyProcessor::new(KEYS_SWAP);
+    static SH_SWAP: SwapHands<5, 1> = swap!(KH_SWAP, [1, 0, 2, 3, 4]);
+    static HANDLERS_SWAP: [&'static dyn Process<5, 1>; 1] = [&SH_SWAP];
+
+    #[test]
+    fn test_swap() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QH)).split() };
+        let keymap: BasicKeymap<5, 1> = BasicKeymap::new(&HANDLERS_SWAP, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0], &[5], &[r!(A)]);
+        tester.test(&[4, 0], &[6, 5], &[r!(B)]); // momentary
+        tester.test(&[2, 2, 0], &[6, 6, 5], &[r!(B)]); // one-shot
+        tester.test(&[0], &[5], &[r!(A)]);
+        tester.test(&[3, 3, 1], &[6, 6, 5], &[r!(A)]); // toggle on
+        tester.test(&[3, 3, 1], &[6, 6, 5], &[r!(B)]); // toggle off
+    }
+
+    static mut QD: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_SOCD: [[&dyn Handle; 3]; 1] = keys!([
+        kc!(A), kc!(D), kc!(X);
+    ]);
+    static KH_SOCD: KeyProcessor<3, 1> = KeyProcessor::new(KEYS_SOCD);
+    static SOCD: Socd<3, 1, 2> = socd!(KH_SOCD, Last, [0, 1]);
+    static HANDLERS_SOCD: [&'static dyn Process<3, 1>; 2] = [&KH_SOCD, &SOCD];
+
+    #[test]
+    fn test_socd() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QD)).split() };
+        let keymap: BasicKeymap<3, 1> = BasicKeymap::new(&HANDLERS_SOCD, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0, 1], &[6, 5], &[r!(D)]); // last input wins
+        tester.test(&[0, 1, 1], &[6, 6, 5], &[r!(A)]); // A again once D is released
+        tester.test(&[0, 2], &[6, 5], &[r!(A), r!(X)]); // other keys unaffected
+    }
+
+    static mut QDS: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_SOCD_SWAP: [[&dyn Handle; 3]; 1] = keys!([
+        kc!(A), kc!(D), kc!(SH_TOGG);
+    ]);
+    static KH_SOCD_SWAP: KeyProcessor<3, 1> = KeyProcessor::new(KEYS_SOCD_SWAP);
+    static SH_SOCD: SwapHands<3, 1> = swap!(KH_SOCD_SWAP, [1, 0, 2]);
+    static SOCD_SWAP: Socd<3, 1, 2> = socd!(SH_SOCD, Last, [0, 1]);
+    static HANDLERS_SOCD_SWAP: [&'static dyn Process<3, 1>; 2] = [&SH_SOCD, &SOCD_SWAP];
+
+    #[test]
+    fn test_socd_swap() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QDS)).split() };
+        let keymap: BasicKeymap<3, 1> = BasicKeymap::new(&HANDLERS_SOCD_SWAP, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[2, 2, 0], &[6, 6, 5], &[r!(D)]); // swapped
+        tester.test(&[0, 1, 1], &[6, 6, 5], &[r!(D)]); // swapped key restored
+    }
+
+    static mut QG: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_GAMING: [[&dyn Handle; 4]; 1] = keys!([
+        ht!(50, kb!(LeftShift), kb!(J)), kc!(LGUI), mdtp!(GAMING), kc!(A);
+    ]);
+    static KH_GAMING: KeyProcessor<4, 1> = KeyProcessor::new(KEYS_GAMING);
+    static CHORD_GAMING: Chord<1> = chrd!(0, 3, [Some(&kc!(Q))]);
+    static HANDLERS_GAMING: [&'static dyn Process<4, 1>; 2] = [&CHORD_GAMING, &KH_GAMING];
+    static BLOCKED: [Keyboard; 2] = [Keyboard::LeftGUI, Keyboard::RightGUI];
+
+    #[test]
+    fn test_gaming() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QG)).split() };
+        let mut keymap: BasicKeymap<4, 1> = BasicKeymap::new(&HANDLERS_GAMING, producer);
+        keymap.set_blocked(&BLOCKED);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0, 3], &[0, 5], &[r!(Q)]); // chord
+        tester.test(&[1, 3], &[0, 5], &[r!(LeftGUI), r!(A)]);
+        tester.test(&[2, 2, 0], &[6, 6, 5], &[r!(J)]); // gaming mode, plain tap
+        tester.test(&[0], &[60], &[r!(J)]); // no hold
+        tester.test(&[0, 3], &[0, 5], &[r!(J), r!(A)]); // no chord
+        tester.test(&[1, 3], &[0, 5], &[r!(A)]); // GUI blocked
+    }
+
+    static mut QT: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_TUNE: [[&dyn Handle; 2]; 1] = keys!([
+        kc!(DT_PRNT), ht!(kb!(LeftShift), kb!(J));
+    ]);
+    static KH_TUNE: KeyProcessor<2, 1> = KeyProcessor::new(KEYS_TUNE);
+    static HANDLERS_TUNE: [&'static dyn Process<2, 1>; 1] = [&KH_TUNE];
+
+    #[test]
+    fn test_tune() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QT)).split() };
+        let keymap: BasicKeymap<2, 1> = BasicKeymap::new(&HANDLERS_TUNE, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0], &[5], &[r!(Keyboard2)]); // prints 200
+        tester.test(&[1, 1], &[199, 5], &[r!(J)]); // tap within the tapping term
+        tester.test(&[0, 0], &[6, 19], &[r!(Keyboard0)]); // second digit
+        tester.test(&[1], &[205], &[r!(LeftShift)]); // hold
+    }
+
+    static mut QT2: Queue<Report, MAX_REPORTS> = Queue::new();
+
+    #[test]
+    fn test_settings() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QT2)).split() };
+        let mut keymap: BasicKeymap<2, 1> = BasicKeymap::new(&HANDLERS_TUNE, producer);
+        keymap.set_settings(Settings::new(100, 50, 5));
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0], &[5], &[r!(Keyboard1)]); // prints 100
+        tester.test(&[1], &[105], &[r!(LeftShift)]); // hold past its own term
+    }
+
+    static mut QC: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_COMBO: [[&dyn Handle; 2]; 1] = keys!([
+        kc!(A), kc!(B);
+    ]);
+    static KH_COMBO: KeyProcessor<2, 1> = KeyProcessor::new(KEYS_COMBO);
+    static CHORD_COMBO: Chord<1> = chrd!(0, 1, [Some(&kc!(Q))]);
+    static HANDLERS_COMBO: [&'static dyn Process<2, 1>; 2] = [&CHORD_COMBO, &KH_COMBO];
+
+    #[test]
+    fn test_combo_term() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QC)).split() };
+        let keymap: BasicKeymap<2, 1> = BasicKeymap::new(&HANDLERS_COMBO, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0, 1], &[0, 5], &[r!(Q)]); // together
+        tester.test(&[0, 1], &[49, 5], &[r!(Q)]); // within the combo term
+        tester.test(&[0, 1], &[50, 5], &[r!(A), r!(B)]); // too late, no chord
+    }
+
+    static mut QP: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_PAST: [[&dyn Handle; 3]; 2] = keys!(
+        [
+            lytp!(5), kc!(A), kc!(B);
+        ],
+        [
+            lytp!(0), kc!(C), kc!(D);
+        ],
+    );
+    static KH_PAST: KeyProcessor<3, 2> = KeyProcessor::new(KEYS_PAST);
+    static CHORD_PAST: Chord<2> = chrd!(1, 2, [None, Some(&kc!(Q))]);
+    static HANDLERS_PAST: [&'static dyn Process<3, 2>; 2] = [&CHORD_PAST, &KH_PAST];
+
+    #[test]
+    fn test_layer_past_keys() {
+        let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QP)).split() };
+        let keymap: BasicKeymap<3, 2> = BasicKeymap::new(&HANDLERS_PAST, producer);
+
+        let mut tester = Tester::new(keymap, consumer);
+        tester.test(&[0, 0, 1], &[6, 6, 5], &[r!(C)]); // layer 5 uses the top layer
+        tester.test(&[1, 2], &[0, 5], &[r!(Q)]); // and its chords
+    }
+
+    static mut QM: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_MACRO: [[&dyn Handle; 5]; 1] = keys!([
+        kc!(DM_REC1), kc!(DM_REC2), kc!(DM_PLY1), kc!(DM_PLY2), kc!(A);
+    ]);
+    static KH_MACRO: KeyProcessor<5, 1> = KeyProcessor::new(KEYS_MACRO);
+    static HANDLERS_MACRO: [&'static dyn Process<5, 1>; 1] = [&KH_MACRO];
+
+    #[test]
+    fn test_macro_chain() {
+        let (producer, mut consumer) = unsafe { (*core::ptr::addr_of_mut!(QM)).split() };
+        let mut keymap: BasicKeymap<5, 1> = BasicKeymap::new(&HANDLERS_MACRO, producer);
+        // Taps key `i`, and counts the A reports sent meanwhile.
+        let mut tap = |keymap: &mut BasicKeymap<5, 1>, i: usize| {
+            let mut switches = [false; 5];
+            switches[i] = true;
+            (0..10).for_each(|_| keymap.tick(&switches));
+            (0..40).for_each(|_| keymap.tick(&[false; 5]));
+            let mut count = 0;
+            while let Some(report) = consumer.dequeue() {
+                count += (report == r!(A)) as usize;
+            }
+            count
+        };
+
+        tap(&mut keymap, 0); // record 1
+        let typed = tap(&mut keymap, 4);
+        assert!(typed > 0);
+        tap(&mut keymap, 0);
+        tap(&mut keymap, 1); // record 2
+        assert_eq!(tap(&mut keymap, 2), typed); // playing 1
+        tap(&mut keymap, 1);
+        assert_eq!(tap(&mut keymap, 3), typed); // 2 plays 1 again
+    }
+
+    struct Motion(Option<(i16, i16)>);
+
+    impl crate::pointing::Sensor for Motion {
+        fn motion(&mut self) -> (i16, i16) {
+            self.0.take().unwrap_or((0, 0))
+        }
+    }
+
+    static mut QPT: Queue<Report, MAX_REPORTS> = Queue::new();
+    static KEYS_POINT: [[&dyn Handle; 3]; 1] = keys!([
+        kc!(DM_REC1), kc!(DM_PLY1), kc!(MS_BTN1);
+    ]);
+    static KH_POINT: KeyProcessor<3, 1> = KeyProcessor::new(KEYS_POINT);
+    static HANDLERS_POINT: [&'static dyn Process<3, 1>; 1] = [&KH_POINT];
+
+    #[test]
+    fn test_point() {
+        let (producer, mut consumer) = unsafe { (*core::ptr::addr_of_mut!(QPT)).split() };
+        let mut keymap: BasicKeymap<3, 1> = BasicKeymap::new(&HANDLERS_POINT, producer);
+        let tap = |keymap: &mut BasicKeymap<3, 1>, i: usize| {
+            let mut switches = [false; 3];
+            switches[i] = true;
+            (0..10).for_each(|_| keymap.tick(&switches));
+            (0..40).for_each(|_| keymap.tick(&[false; 3]));
+        };
+        let moved = Report::Mouse(WheelMouseReport {
+            buttons: 1,
+            x: 10,
+            ..Default::default()
+        });
+
+        tap(&mut keymap, 0); // record 1
+        (0..10).for_each(|_| keymap.tick(&[false, false, true]));
+        while consumer.dequeue().is_some() {}
+        // Motion and held mouse buttons make one report.
+        keymap.point(&mut Motion(Some((10, 0))));
+        keymap.tick(&[false, false, true]);
+        assert_eq!(consumer.dequeue(), Some(moved));
+        assert_eq!(consumer.dequeue(), None);
+        (0..40).for_each(|_| keymap.tick(&[false; 3]));
+        tap(&mut keymap, 0);
+        while consumer.dequeue().is_some() {}
+
+        // The motion was recorded.
+        tap(&mut keymap, 1);
+        let mut played = 0;
+        while let Some(report) = consumer.dequeue() {
+            played += (report == moved) as usize;
+        }
+        assert_eq!(played, 1);
+    }
 }
@@ -0,0 +1,74 @@
+use crate::state::{State, MAX_LAYERS};
+
+// Activates layer `then` whenever all of `layers` are active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    layers: u32,
+    then: usize,
+}
+
+impl Condition {
+    pub const fn new(layers: &[usize], then: usize) -> Condition {
+        let mut mask = 0;
+        let mut i = 0;
+        assert!(then < MAX_LAYERS);
+        while i < layers.len() {
+            assert!(layers[i] < MAX_LAYERS);
+            mask |= 1 << layers[i];
+            i += 1;
+        }
+        Condition { layers: mask, then }
+    }
+}
+
+pub fn apply(conditions: &[Condition], state: &mut State) {
+    for condition in conditions {
+        match state.layers() & condition.layers == condition.layers {
+            true => state.layer_on(condition.then),
+            false => state.layer_off(condition.then),
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! cond {
+    ([$($x:literal),+ $(,)?] => $then:literal) => {
+        $crate::layer::Condition::new(&[$($x),+], $then)
+    };
+}
+
+// Tri Layer
+#[macro_export]
+macro_rules! tri {
+    ($x0:literal, $x1:literal, $then:literal) => {
+        $crate::cond!([$x0, $x1] => $then)
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static CONDITIONS: [Condition; 1] = [tri!(1, 2, 3)];
+
+    #[test]
+    fn test_tri() {
+        let mut state = State::new();
+        state.layer_on(1);
+        apply(&CONDITIONS, &mut state);
+        assert_eq!(state.layer(), 1);
+
+        state.layer_on(2);
+        apply(&CONDITIONS, &mut state);
+        assert_eq!(state.layer(), 3);
+
+        state.layer_off(1);
+        apply(&CONDITIONS, &mut state);
+        assert_eq!(state.layer(), 2);
+
+        // Layers past the u32 are ignored.
+        state.layer_on(40);
+        state.set_layer(40);
+        assert_eq!(state.layer(), 2);
+    }
+}
@@ -1,10 +1,30 @@
 #![allow(unused_imports)]
 #![allow(unused_macros)]
 #![no_std]
-pub mod handler;
+pub mod access;
+pub mod action;
+pub mod command;
 pub mod debouncer;
 pub mod event;
 pub mod function;
+pub mod gamepad;
+pub mod handler;
+pub mod indicator;
+pub mod key_override;
+pub mod keycode;
 pub mod keymap;
+pub mod layer;
+pub mod lock;
+pub mod mode;
+pub mod mouse;
+pub mod pointing;
 pub mod processor;
+pub mod recorder;
+pub mod repeat;
 pub mod report;
+pub mod sequence;
+pub mod settings;
+pub mod state;
+pub mod storage;
+pub mod unicode;
+pub mod via;
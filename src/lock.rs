@@ -0,0 +1,79 @@
+use crate::event::Event;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lock {
+    // Lock the current layer on.
+    Layer,
+    // Latch the next pressed key down.
+    Key,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Latch {
+    // Still held since it was locked.
+    Held,
+    // Released, but kept down.
+    Latched,
+    // Pressed again, let go on release.
+    Unlocking,
+}
+
+// Keeps locked keys pressed by rewriting their events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyLock<const N: usize> {
+    armed: bool,
+    keys: [Option<(Latch, usize)>; N],
+}
+
+impl<const N: usize> Default for KeyLock<N> {
+    fn default() -> KeyLock<N> {
+        KeyLock::new()
+    }
+}
+
+impl<const N: usize> KeyLock<N> {
+    pub const fn new() -> KeyLock<N> {
+        KeyLock {
+            armed: false,
+            keys: [None; N],
+        }
+    }
+
+    // Arms the lock for the next pressed key, or disarms it.
+    pub fn arm(&mut self) {
+        self.armed = !self.armed;
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    pub fn is_locked(&self, i: usize) -> bool {
+        self.keys[i].is_some()
+    }
+
+    pub fn lock(&mut self, i: usize) {
+        self.armed = false;
+        self.keys[i] = Some((Latch::Held, 0));
+    }
+
+    pub fn update(&mut self, events: &mut [Event; N]) {
+        for (key, event) in self.keys.iter_mut().zip(events) {
+            let Some((latch, held)) = key else {
+                continue;
+            };
+            *held = held.saturating_add(1);
+            *latch = match (*latch, *event) {
+                (Latch::Unlocking, Event::Release(_)) => {
+                    *event = Event::Release(*held);
+                    *key = None;
+                    continue;
+                }
+                (Latch::Held, Event::Release(_)) => Latch::Latched,
+                (Latch::Latched, Event::Press(_)) => Latch::Unlocking,
+                (latch, _) => latch,
+            };
+            *event = Event::Pressed(*held);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+// Keymap modes that can be turned on and off at runtime.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modes(u16);
+
+impl Modes {
+    pub const NONE: Modes = Modes(0);
+    // Accept a press only after the key was held for a while.
+    pub const SLOW_KEYS: Modes = Modes(1 << 0);
+    // Ignore a press that follows the release of the same key too closely.
+    pub const BOUNCE_KEYS: Modes = Modes(1 << 1);
+    // Keep tapped modifiers down until the next key.
+    pub const STICKY_MODIFIERS: Modes = Modes(1 << 2);
+    // Block selected keys, make hold-taps plain keys and disable chords.
+    pub const GAMING: Modes = Modes(1 << 3);
+
+    pub const fn from_bits(bits: u16) -> Modes {
+        Modes(bits)
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    pub const fn union(self, other: Modes) -> Modes {
+        Modes(self.0 | other.0)
+    }
+
+    pub const fn difference(self, other: Modes) -> Modes {
+        Modes(self.0 & !other.0)
+    }
+
+    pub const fn toggle(self, other: Modes) -> Modes {
+        Modes(self.0 ^ other.0)
+    }
+
+    pub const fn contains(self, other: Modes) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
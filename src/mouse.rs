@@ -0,0 +1,313 @@
+use crate::event::Event;
+use crate::keymap::Reports;
+use crate::report::{Report, WheelMouseReport};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+    Button1,
+    Button2,
+    Button3,
+    Button4,
+    Button5,
+    Accel0,
+    Accel1,
+    Accel2,
+    Scroll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Constant,
+    Linear,
+    Quadratic,
+}
+
+// Speed of movement as a function of how long the keys have been held.
+// Movement is sent on press, then every `interval` ticks after `delay`;
+// speed grows from `speed` to `max_speed` over `time_to_max` ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    delay: usize,
+    interval: usize,
+    speed: u8,
+    max_speed: u8,
+    time_to_max: usize,
+    curve: Curve,
+}
+
+impl Profile {
+    pub const fn new(
+        delay: usize,
+        interval: usize,
+        speed: u8,
+        max_speed: u8,
+        time_to_max: usize,
+        curve: Curve,
+    ) -> Profile {
+        Profile {
+            delay,
+            interval,
+            speed,
+            max_speed,
+            time_to_max,
+            curve,
+        }
+    }
+
+    pub fn due(&self, t: usize) -> bool {
+        t == 0 || (t >= self.delay && (t - self.delay).is_multiple_of(self.interval.max(1)))
+    }
+
+    pub fn speed(&self, t: usize) -> u8 {
+        let t = t.saturating_sub(self.delay);
+        let span = self.max_speed.saturating_sub(self.speed) as usize;
+        let step = match self.curve {
+            Curve::Constant => 0,
+            _ if t >= self.time_to_max => span,
+            Curve::Linear => span * t / self.time_to_max,
+            Curve::Quadratic => span * t * t / (self.time_to_max * self.time_to_max),
+        };
+        self.speed.saturating_add(step as u8)
+    }
+
+    // Constant speed selected by the Accel keys.
+    pub fn constant(&self, accel: u8) -> u8 {
+        match accel {
+            0 => self.speed,
+            1 => self.speed / 2 + self.max_speed / 2,
+            _ => self.max_speed,
+        }
+    }
+}
+
+// Collects the mouse keys handled in one tick into a single report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseKeys {
+    cursor: Profile,
+    wheel: Profile,
+    normalize: bool,
+    buttons: u8,
+    x: i8,
+    y: i8,
+    h: i8,
+    v: i8,
+    cursor_time: Option<usize>,
+    wheel_time: Option<usize>,
+    accel: Option<u8>,
+    scroll: bool,
+}
+
+impl Default for MouseKeys {
+    fn default() -> MouseKeys {
+        MouseKeys::new(
+            Profile::new(10, 16, 8, 80, 480, Curve::Linear),
+            Profile::new(10, 80, 1, 8, 3200, Curve::Linear),
+            true,
+        )
+    }
+}
+
+impl MouseKeys {
+    pub const fn new(cursor: Profile, wheel: Profile, normalize: bool) -> MouseKeys {
+        MouseKeys {
+            cursor,
+            wheel,
+            normalize,
+            buttons: 0,
+            x: 0,
+            y: 0,
+            h: 0,
+            v: 0,
+            cursor_time: None,
+            wheel_time: None,
+            accel: None,
+            scroll: false,
+        }
+    }
+
+    pub fn press(&mut self, key: MouseKey, event: &Event) {
+        let t = match event {
+            Event::Pressed(i) => *i,
+            _ => 0,
+        };
+        match key {
+            MouseKey::Up => self.cursor(0, -1, t),
+            MouseKey::Down => self.cursor(0, 1, t),
+            MouseKey::Left => self.cursor(-1, 0, t),
+            MouseKey::Right => self.cursor(1, 0, t),
+            MouseKey::WheelUp => self.wheel(0, 1, t),
+            MouseKey::WheelDown => self.wheel(0, -1, t),
+            MouseKey::WheelLeft => self.wheel(-1, 0, t),
+            MouseKey::WheelRight => self.wheel(1, 0, t),
+            MouseKey::Button1 => self.buttons |= 1 << 0,
+            MouseKey::Button2 => self.buttons |= 1 << 1,
+            MouseKey::Button3 => self.buttons |= 1 << 2,
+            MouseKey::Button4 => self.buttons |= 1 << 3,
+            MouseKey::Button5 => self.buttons |= 1 << 4,
+            MouseKey::Accel0 => self.accel = self.accel.max(Some(0)),
+            MouseKey::Accel1 => self.accel = self.accel.max(Some(1)),
+            MouseKey::Accel2 => self.accel = self.accel.max(Some(2)),
+            MouseKey::Scroll => self.scroll = true,
+        }
+    }
+
+    fn cursor(&mut self, x: i8, y: i8, t: usize) {
+        self.x += x;
+        self.y += y;
+        self.cursor_time = self.cursor_time.max(Some(t));
+    }
+
+    fn wheel(&mut self, h: i8, v: i8, t: usize) {
+        self.h += h;
+        self.v += v;
+        self.wheel_time = self.wheel_time.max(Some(t));
+    }
+
+    // Whether the pointing device should scroll this tick.
+    pub fn scroll(&self) -> bool {
+        self.scroll
+    }
+
+    // Builds the report for this tick and clears the collected keys.
+    pub fn report(&mut self) -> Option<WheelMouseReport> {
+        let mut report = WheelMouseReport {
+            buttons: self.buttons,
+            ..Default::default()
+        };
+        if let Some(t) = self.cursor_time.filter(|t| self.cursor.due(*t)) {
+            let speed = self
+                .accel
+                .map_or(self.cursor.speed(t), |a| self.cursor.constant(a));
+            (report.x, report.y) = self.scale(self.x, self.y, speed);
+        }
+        if let Some(t) = self.wheel_time.filter(|t| self.wheel.due(*t)) {
+            let speed = self
+                .accel
+                .map_or(self.wheel.speed(t), |a| self.wheel.constant(a));
+            (report.horizontal_wheel, report.vertical_wheel) = self.scale(self.h, self.v, speed);
+        }
+
+        *self = MouseKeys::new(self.cursor, self.wheel, self.normalize);
+        (report != WheelMouseReport::default()).then_some(report)
+    }
+
+    fn scale(&self, x: i8, y: i8, speed: u8) -> (i8, i8) {
+        let (x, y) = (x.signum(), y.signum());
+        let speed = match self.normalize && x != 0 && y != 0 {
+            // 181 / 256 ~ 1 / sqrt(2)
+            true => (speed as u16 * 181 / 256).max(1) as u8,
+            false => speed,
+        };
+        let speed = speed.min(i8::MAX as u8) as i8;
+        (x * speed, y * speed)
+    }
+}
+
+// Adds up the mouse reports of a tick into the first one, so that mouse
+// keys, the pointing device and macros send a single report.
+pub fn merge(reports: &mut Reports) {
+    let mut merged: Option<(i16, i16, i16, i16, u8)> = None;
+    reports.retain(|report| {
+        let Report::Mouse(r) = report else {
+            return true;
+        };
+        let first = merged.is_none();
+        let (x, y, v, h, buttons) = merged.get_or_insert((0, 0, 0, 0, 0));
+        *x += r.x as i16;
+        *y += r.y as i16;
+        *v += r.vertical_wheel as i16;
+        *h += r.horizontal_wheel as i16;
+        *buttons |= r.buttons;
+        first
+    });
+
+    let Some((x, y, v, h, buttons)) = merged else {
+        return;
+    };
+    let clamp = |v: i16| v.clamp(-(i8::MAX as i16), i8::MAX as i16) as i8;
+    for report in reports.iter_mut() {
+        if let Report::Mouse(r) = report {
+            *r = WheelMouseReport {
+                buttons,
+                x: clamp(x),
+                y: clamp(y),
+                vertical_wheel: clamp(v),
+                horizontal_wheel: clamp(h),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn curves() {
+        let linear = Profile::new(10, 16, 8, 80, 480, Curve::Linear);
+        assert_eq!(linear.speed(0), 8);
+        assert_eq!(linear.speed(250), 44);
+        assert_eq!(linear.speed(1000), 80);
+
+        let quadratic = Profile::new(10, 16, 8, 80, 480, Curve::Quadratic);
+        assert_eq!(quadratic.speed(250), 26);
+        assert_eq!(quadratic.speed(1000), 80);
+
+        let constant = Profile::new(10, 16, 8, 80, 480, Curve::Constant);
+        assert_eq!(constant.speed(1000), 8);
+        assert_eq!(constant.constant(2), 80);
+
+        assert!(linear.due(0));
+        assert!(!linear.due(5));
+        assert!(linear.due(10));
+        assert!(linear.due(26));
+    }
+
+    #[test]
+    fn report() {
+        let mut mouse = MouseKeys::default();
+        assert_eq!(mouse.report(), None);
+
+        mouse.press(MouseKey::Right, &Event::Press(0));
+        mouse.press(MouseKey::Up, &Event::Press(0));
+        mouse.press(MouseKey::Button1, &Event::Press(0));
+        let report = mouse.report().unwrap();
+        assert_eq!((report.buttons, report.x, report.y), (1, 5, -5));
+
+        mouse.press(MouseKey::Right, &Event::Pressed(5));
+        assert_eq!(mouse.report(), None);
+
+        mouse.press(MouseKey::Left, &Event::Pressed(10));
+        mouse.press(MouseKey::Accel2, &Event::Pressed(10));
+        assert_eq!(mouse.report().unwrap().x, -80);
+
+        mouse.press(MouseKey::WheelDown, &Event::Press(0));
+        assert_eq!(mouse.report().unwrap().vertical_wheel, -1);
+    }
+
+    #[test]
+    fn merge() {
+        let mut reports = Reports::new();
+        let mouse = |buttons, x, y| {
+            Report::Mouse(WheelMouseReport {
+                buttons,
+                x,
+                y,
+                ..Default::default()
+            })
+        };
+        reports.push(mouse(1, 100, 0)).unwrap();
+        reports.push(Report::Custom(0)).unwrap();
+        reports.push(mouse(2, 100, -3)).unwrap();
+        super::merge(&mut reports);
+        assert_eq!(&reports[..], &[mouse(3, 127, -3), Report::Custom(0)]);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::function::Function;
+use crate::report::WheelMouseReport;
+
+// Source of relative motion, e.g. a trackball or trackpoint sensor.
+pub trait Sensor {
+    fn motion(&mut self) -> (i16, i16);
+}
+
+// Turns sensor motion into mouse reports.
+// Counts are scaled from `sensor_cpi` to `cpi`; in scroll mode every
+// `scroll_divisor` counts make one wheel step. With an auto layer, the
+// layer is turned on when motion starts and off again after `timeout`
+// ticks without motion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pointing {
+    sensor_cpi: u16,
+    cpi: u16,
+    scroll_divisor: u16,
+    auto_layer: Option<usize>,
+    timeout: usize,
+    scroll: bool,
+    remainder: (i32, i32),
+    idle: usize,
+    active: bool,
+}
+
+impl Default for Pointing {
+    fn default() -> Pointing {
+        Pointing::new(800, 800, 8, None, 0)
+    }
+}
+
+impl Pointing {
+    pub const fn new(
+        sensor_cpi: u16,
+        cpi: u16,
+        scroll_divisor: u16,
+        auto_layer: Option<usize>,
+        timeout: usize,
+    ) -> Pointing {
+        Pointing {
+            sensor_cpi,
+            cpi,
+            scroll_divisor,
+            auto_layer,
+            timeout,
+            scroll: false,
+            remainder: (0, 0),
+            idle: 0,
+            active: false,
+        }
+    }
+
+    pub fn set_cpi(&mut self, cpi: u16) {
+        self.cpi = cpi;
+    }
+
+    pub fn set_scroll(&mut self, scroll: bool) {
+        if scroll != self.scroll {
+            self.remainder = (0, 0);
+        }
+        self.scroll = scroll;
+    }
+
+    pub fn keep_alive(&mut self) {
+        self.idle = 0;
+    }
+
+    pub fn update(&mut self, motion: (i16, i16)) -> (Option<WheelMouseReport>, Option<Function>) {
+        if motion == (0, 0) {
+            self.idle = self.idle.saturating_add(1);
+            return (None, self.sleep());
+        }
+        self.idle = 0;
+
+        let (x, y) = match self.scroll {
+            true => self.scale(motion, 1, self.scroll_divisor),
+            false => self.scale(motion, self.cpi, self.sensor_cpi),
+        };
+        let report = match self.scroll {
+            // Moving up scrolls up.
+            true => WheelMouseReport {
+                horizontal_wheel: x,
+                vertical_wheel: -y,
+                ..Default::default()
+            },
+            false => WheelMouseReport {
+                x,
+                y,
+                ..Default::default()
+            },
+        };
+        let report = (report != WheelMouseReport::default()).then_some(report);
+        (report, self.wake())
+    }
+
+    fn scale(&mut self, (x, y): (i16, i16), num: u16, den: u16) -> (i8, i8) {
+        let den = den.max(1) as i32;
+        let x = self.remainder.0 + x as i32 * num as i32;
+        let y = self.remainder.1 + y as i32 * num as i32;
+        self.remainder = (x % den, y % den);
+        let clamp = |v: i32| (v / den).clamp(-(i8::MAX as i32), i8::MAX as i32) as i8;
+        (clamp(x), clamp(y))
+    }
+
+    fn wake(&mut self) -> Option<Function> {
+        let auto_layer = self.auto_layer.filter(|_| !self.active)?;
+        self.active = true;
+        Some(Function::LayerOn(auto_layer))
+    }
+
+    fn sleep(&mut self) -> Option<Function> {
+        let auto_layer = self.auto_layer.filter(|_| self.active)?;
+        if self.idle < self.timeout {
+            return None;
+        }
+        self.active = false;
+        Some(Function::LayerOff(auto_layer))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Synthetic<'a>(core::slice::Iter<'a, (i16, i16)>);
+
+    impl Sensor for Synthetic<'_> {
+        fn motion(&mut self) -> (i16, i16) {
+            self.0.next().copied().unwrap_or((0, 0))
+        }
+    }
+
+    #[test]
+    fn cpi() {
+        let mut pointing = Pointing::new(1600, 400, 8, None, 0);
+        let mut sensor = Synthetic([(3, -3), (1, -1), (400, 0)].iter());
+
+        assert!(matches!(pointing.update(sensor.motion()), (None, None)));
+        let (report, _) = pointing.update(sensor.motion());
+        assert_eq!(report.map(|r| (r.x, r.y)), Some((1, -1)));
+        let (report, _) = pointing.update(sensor.motion());
+        assert_eq!(report.map(|r| (r.x, r.y)), Some((100, 0)));
+    }
+
+    #[test]
+    fn scroll() {
+        let mut pointing = Pointing::new(800, 800, 8, None, 0);
+        let mut sensor = Synthetic([(0, -12), (0, -4), (20, 0)].iter());
+        pointing.set_scroll(true);
+
+        let (report, _) = pointing.update(sensor.motion());
+        assert_eq!(report.map(|r| r.vertical_wheel), Some(1));
+        let (report, _) = pointing.update(sensor.motion());
+        assert_eq!(report.map(|r| r.vertical_wheel), Some(1));
+        let (report, _) = pointing.update(sensor.motion());
+        assert_eq!(report.map(|r| (r.x, r.horizontal_wheel)), Some((0, 2)));
+    }
+
+    #[test]
+    fn auto_layer() {
+        let mut pointing = Pointing::new(800, 800, 8, Some(3), 2);
+        let mut sensor = Synthetic([(1, 0), (1, 0)].iter());
+
+        let (_, layer) = pointing.update(sensor.motion());
+        assert!(matches!(layer, Some(Function::LayerOn(3))));
+        let (_, layer) = pointing.update(sensor.motion());
+        assert!(layer.is_none());
+        let (_, layer) = pointing.update(sensor.motion());
+        assert!(layer.is_none());
+        let (_, layer) = pointing.update(sensor.motion());
+        assert!(matches!(layer, Some(Function::LayerOff(3))));
+    }
+}
@@ -3,15 +3,19 @@ use itertools::izip;
 use crate::event::Event;
 use crate::function::Function;
 use crate::handler::Handle;
+use crate::state::State;
 
 pub mod chord;
+pub mod dynamic;
+pub mod socd;
+pub mod swap;
 
 pub trait Process<const N: usize, const L: usize>: Sync {
     fn process(
         &self,
         handlers: &mut [Option<&'static dyn Handle>; N],
         events: &[Event; N],
-        layer: usize,
+        state: &State,
     );
 }
 
@@ -23,6 +27,11 @@ impl<const N: usize, const L: usize> KeyProcessor<N, L> {
     pub const fn new(keys: [[&'static dyn Handle; N]; L]) -> KeyProcessor<N, L> {
         KeyProcessor { keys }
     }
+
+    // Layers past the keymap, e.g. set by an action, use the top one.
+    pub fn key(&self, layer: usize, i: usize) -> &'static dyn Handle {
+        self.keys[layer.min(L - 1)][i]
+    }
 }
 
 impl<const N: usize, const L: usize> Process<N, L> for KeyProcessor<N, L> {
@@ -30,15 +39,13 @@ impl<const N: usize, const L: usize> Process<N, L> for KeyProcessor<N, L> {
         &self,
         handlers: &mut [Option<&'static dyn Handle>; N],
         events: &[Event; N],
-        layer: usize,
+        state: &State,
     ) {
-        let keys = &self.keys[layer];
+        let keys = &self.keys[state.layer().min(L - 1)];
 
         for (handler, event, key) in izip!(handlers, events, keys) {
-            if matches!(event, Event::Press(_)) {
-                if handler.is_none() {
-                    *handler = Some(*key);
-                }
+            if matches!(event, Event::Press(_)) && handler.is_none() {
+                *handler = Some(*key);
             }
         }
     }
@@ -4,8 +4,12 @@ use core::sync::atomic::{AtomicBool, Ordering};
 use crate::event::Event;
 use crate::function::Function;
 use crate::handler::Handle;
+use crate::mode::Modes;
 use crate::processor::Process;
+use crate::state::State;
 
+// The handler of the layer replaces those of both keys when they are pressed
+// within the combo term.
 pub struct Chord<const L: usize> {
     ids: (usize, usize),
     handlers: [Option<&'static dyn Handle>; L],
@@ -22,17 +26,25 @@ impl<const N: usize, const L: usize> Process<N, L> for Chord<L> {
         &self,
         handlers: &mut [Option<&'static dyn Handle>; N],
         events: &[Event; N],
-        layer: usize,
+        state: &State,
     ) {
+        if state.modes().contains(Modes::GAMING) {
+            return;
+        }
+        let layer = state.layer().min(L - 1);
         let (id0, id1) = self.ids;
         let event0 = events[id0];
         let event1 = events[id1];
+        let term = state.settings().combo_term();
         if let Some(handler) = self.handlers[layer] {
             match (event0, event1) {
-                (Event::Pressed(_), Event::Pressed(_))
-                | (Event::Pressed(_), Event::Press(_))
-                | (Event::Press(_), Event::Pressed(_))
-                | (Event::Press(_), Event::Press(_)) => {
+                (Event::Pressed(i), Event::Press(_)) | (Event::Press(_), Event::Pressed(i))
+                    if i < term =>
+                {
+                    handlers[id0] = Some(handler);
+                    handlers[id1] = Some(handler);
+                }
+                (Event::Press(_), Event::Press(_)) => {
                     handlers[id0] = Some(handler);
                     handlers[id1] = Some(handler);
                 }
@@ -0,0 +1,222 @@
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use itertools::izip;
+
+use crate::event::Event;
+use crate::function::Function;
+use crate::handler::Handle;
+use crate::keycode::{self, TRANSPARENT};
+use crate::processor::Process;
+use crate::state::State;
+
+// A key bound to a keycode that can be changed at runtime, in a keymap of
+// `L` layers.
+pub struct Code<const L: usize>(AtomicU16);
+
+impl<const L: usize> Code<L> {
+    pub const fn new(code: u16) -> Code<L> {
+        Code(AtomicU16::new(code))
+    }
+
+    pub fn get(&self) -> u16 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, code: u16) {
+        self.0.store(code, Ordering::Relaxed);
+    }
+}
+
+impl<const L: usize> Handle for Code<L> {
+    fn handle(&self, event: &Event, state: &State) -> Option<Function> {
+        keycode::decode(self.get(), L).handle(event, state)
+    }
+}
+
+// Header of a serialized keymap: magic, format version, layers and keys.
+const MAGIC: [u8; 2] = *b"RK";
+const VERSION: u8 = 1;
+const HEADER: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    // The buffer is too small, or the data is not as long as the header says.
+    Length,
+    Magic,
+    Version,
+    // The data was saved from a keymap of another size.
+    Shape,
+    // Unsupported keycode at this index, counted over all layers.
+    Keycode(usize),
+}
+
+// Keymap held in RAM as keycodes, starting from `defaults`.
+// It serializes to a header followed by little-endian keycodes, layer by layer.
+pub struct DynamicKeymap<const N: usize, const L: usize> {
+    defaults: &'static [[u16; N]; L],
+    keys: [[Code<L>; N]; L],
+}
+
+impl<const N: usize, const L: usize> DynamicKeymap<N, L> {
+    pub const fn new(defaults: &'static [[u16; N]; L]) -> DynamicKeymap<N, L> {
+        let mut keys = [const { [const { Code::new(0) }; N] }; L];
+        let mut layer = 0;
+        while layer < L {
+            let mut i = 0;
+            while i < N {
+                keys[layer][i] = Code::new(defaults[layer][i]);
+                i += 1;
+            }
+            layer += 1;
+        }
+        DynamicKeymap { defaults, keys }
+    }
+
+    pub fn key(&self, layer: usize, i: usize) -> &Code<L> {
+        &self.keys[layer][i]
+    }
+
+    pub fn get(&self, layer: usize, i: usize) -> u16 {
+        self.keys[layer][i].get()
+    }
+
+    // Keycodes of layers past the keymap are ignored. Returns the keycode
+    // the key is left with.
+    pub fn set(&self, layer: usize, i: usize, code: u16) -> u16 {
+        if keycode::layer(code).is_none_or(|layer| layer < L) {
+            self.keys[layer][i].set(code);
+        }
+        self.get(layer, i)
+    }
+
+    // Number of bytes taken by the serialized keymap.
+    pub const SIZE: usize = HEADER + N * L * 2;
+
+    pub fn save(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let bytes = bytes.get_mut(..Self::SIZE).ok_or(Error::Length)?;
+        let (header, data) = bytes.split_at_mut(HEADER);
+        header[..2].copy_from_slice(&MAGIC);
+        header[2] = VERSION;
+        header[3] = L as u8;
+        header[4..].copy_from_slice(&(N as u16).to_le_bytes());
+        for (chunk, key) in data.chunks_exact_mut(2).zip(self.keys.iter().flatten()) {
+            chunk.copy_from_slice(&key.get().to_le_bytes());
+        }
+        Ok(Self::SIZE)
+    }
+
+    // Replaces the keymap with serialized data, leaving it untouched if
+    // the data is not valid.
+    pub fn load(&self, bytes: &[u8]) -> Result<(), Error> {
+        let (header, data) = bytes.split_at_checked(HEADER).ok_or(Error::Length)?;
+        if header[..2] != MAGIC {
+            return Err(Error::Magic);
+        }
+        if header[2] != VERSION {
+            return Err(Error::Version);
+        }
+        if header[3] as usize != L || u16::from_le_bytes([header[4], header[5]]) as usize != N {
+            return Err(Error::Shape);
+        }
+        if data.len() != N * L * 2 {
+            return Err(Error::Length);
+        }
+
+        let codes = data
+            .chunks_exact(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]));
+        if let Some(i) = codes.clone().position(|code| !keycode::is_valid(code, L)) {
+            return Err(Error::Keycode(i));
+        }
+        for (key, code) in self.keys.iter().flatten().zip(codes) {
+            key.set(code);
+        }
+        Ok(())
+    }
+
+    pub fn reset(&self) {
+        for (keys, defaults) in self.keys.iter().zip(self.defaults) {
+            for (key, code) in keys.iter().zip(defaults) {
+                key.set(*code);
+            }
+        }
+    }
+}
+
+// Looks up handlers in a `DynamicKeymap`, like `KeyProcessor` does in a
+// static one. Transparent keys fall through to the active layers below.
+pub struct DynamicKeys<const N: usize, const L: usize> {
+    keymap: &'static DynamicKeymap<N, L>,
+}
+
+impl<const N: usize, const L: usize> DynamicKeys<N, L> {
+    pub const fn new(keymap: &'static DynamicKeymap<N, L>) -> DynamicKeys<N, L> {
+        DynamicKeys { keymap }
+    }
+}
+
+impl<const N: usize, const L: usize> Process<N, L> for DynamicKeys<N, L> {
+    fn process(
+        &self,
+        handlers: &mut [Option<&'static dyn Handle>; N],
+        events: &[Event; N],
+        state: &State,
+    ) {
+        for (i, (handler, event)) in izip!(handlers, events).enumerate() {
+            if !matches!(event, Event::Press(_)) || handler.is_some() {
+                continue;
+            }
+            let layer = (0..=state.layer().min(L - 1))
+                .rev()
+                .filter(|layer| *layer == 0 || state.is_active(*layer))
+                .find(|layer| self.keymap.get(*layer, i) != TRANSPARENT);
+            *handler = layer.map(|layer| self.keymap.key(layer, i) as &dyn Handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static DEFAULTS: [[u16; 3]; 2] = [[0x04, 0x2205, 0x5221], [0x01, 0x0106, 0x4107]];
+    static KEYMAP: DynamicKeymap<3, 2> = DynamicKeymap::new(&DEFAULTS);
+    static OTHER: DynamicKeymap<3, 2> = DynamicKeymap::new(&[[0; 3]; 2]);
+    static SMALL: DynamicKeymap<2, 2> = DynamicKeymap::new(&[[0; 2]; 2]);
+
+    #[test]
+    fn test_serialize() {
+        let mut bytes = [0; DynamicKeymap::<3, 2>::SIZE];
+        assert_eq!(KEYMAP.save(&mut bytes[..10]), Err(Error::Length));
+        assert_eq!(KEYMAP.save(&mut bytes), Ok(18));
+        assert_eq!(bytes[..8], [b'R', b'K', 1, 2, 3, 0, 0x04, 0x00]);
+
+        assert_eq!(OTHER.load(&bytes), Ok(()));
+        let mut copy = [0; 18];
+        assert_eq!(OTHER.save(&mut copy), Ok(18));
+        assert_eq!(copy, bytes);
+        assert_eq!(OTHER.get(1, 2), 0x4107);
+        assert_eq!(SMALL.load(&bytes), Err(Error::Shape));
+        assert_eq!(OTHER.load(&bytes[..17]), Err(Error::Length));
+
+        let mut bad = bytes;
+        bad[2] = 2;
+        assert_eq!(OTHER.load(&bad), Err(Error::Version));
+        bad = bytes;
+        bad[0] = 0;
+        assert_eq!(OTHER.load(&bad), Err(Error::Magic));
+
+        // Nothing is loaded when a keycode is not supported.
+        OTHER.reset();
+        bad = bytes;
+        bad[14..16].copy_from_slice(&0x7FFFu16.to_le_bytes());
+        assert_eq!(OTHER.load(&bad), Err(Error::Keycode(4)));
+        assert_eq!(OTHER.get(0, 0), 0);
+
+        // Nor when a layer keycode is past the layers, here MO(2).
+        bad = bytes;
+        bad[8..10].copy_from_slice(&0x5222u16.to_le_bytes());
+        assert_eq!(OTHER.load(&bad), Err(Error::Keycode(1)));
+        assert_eq!(OTHER.get(0, 0), 0);
+    }
+}
@@ -0,0 +1,146 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::event::Event;
+use crate::function::Function;
+use crate::handler::Handle;
+use crate::processor::Process;
+use crate::state::State;
+
+// How simultaneous presses within a set are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    // The last pressed key wins.
+    Last,
+    // The first pressed key wins.
+    First,
+    // No key wins while several are held.
+    Neutral,
+}
+
+struct Suppressed;
+
+impl Handle for Suppressed {
+    fn handle(&self, _event: &Event, _state: &State) -> Option<Function> {
+        None
+    }
+}
+
+static SUPPRESSED: Suppressed = Suppressed;
+
+// Simultaneous opposing direction cleaning over a set of mutually exclusive
+// keys. Losing keys are silenced, and get their handler back from `keys`
+// once they win again, e.g. when the winning key is released. `keys` is the
+// processor looking up handlers, e.g. a `KeyProcessor` or `SwapHands`, and
+// must come before this one in the processors.
+pub struct Socd<const N: usize, const L: usize, const K: usize> {
+    keys: &'static dyn Process<N, L>,
+    set: [usize; K],
+    policy: Policy,
+    // Keys of the set silenced here, a bit per key.
+    suppressed: AtomicU32,
+}
+
+impl<const N: usize, const L: usize, const K: usize> Socd<N, L, K> {
+    pub const fn new(
+        keys: &'static dyn Process<N, L>,
+        set: [usize; K],
+        policy: Policy,
+    ) -> Socd<N, L, K> {
+        assert!(K <= u32::BITS as usize);
+        Socd {
+            keys,
+            set,
+            policy,
+            suppressed: AtomicU32::new(0),
+        }
+    }
+
+    // Handler `keys` gives key `i` when pressed.
+    fn lookup(&self, i: usize, state: &State) -> Option<&'static dyn Handle> {
+        let mut handlers = [None; N];
+        let mut events = [Event::default(); N];
+        events[i] = Event::Press(0);
+        self.keys.process(&mut handlers, &events, state);
+        handlers[i]
+    }
+}
+
+// Index into `held` of the key to keep, `held` being the hold durations of
+// the keys of a set, if held.
+pub fn winner(held: &[Option<usize>], policy: Policy) -> Option<usize> {
+    let mut keys = held
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| Some((i, (*t)?)));
+    match policy {
+        Policy::Last => keys.min_by_key(|(_, t)| *t),
+        Policy::First => keys.max_by_key(|(_, t)| *t),
+        Policy::Neutral => keys.next().filter(|_| held.iter().flatten().count() == 1),
+    }
+    .map(|(i, _)| i)
+}
+
+impl<const N: usize, const L: usize, const K: usize> Process<N, L> for Socd<N, L, K> {
+    fn process(
+        &self,
+        handlers: &mut [Option<&'static dyn Handle>; N],
+        events: &[Event; N],
+        state: &State,
+    ) {
+        let held = self.set.map(|i| match events[i] {
+            Event::Press(_) => Some(0),
+            Event::Pressed(t) => Some(t),
+            _ => None,
+        });
+        let winner = winner(&held, self.policy);
+
+        let mut suppressed = self.suppressed.load(Ordering::Relaxed);
+        for (k, i) in self.set.into_iter().enumerate() {
+            let bit = 1 << k;
+            if held[k].is_none() {
+                suppressed &= !bit;
+                continue;
+            }
+            match (winner == Some(k), suppressed & bit != 0) {
+                (true, true) => {
+                    handlers[i] = self.lookup(i, state);
+                    suppressed &= !bit;
+                }
+                (false, false) => {
+                    handlers[i] = Some(&SUPPRESSED);
+                    suppressed |= bit;
+                }
+                _ => {}
+            }
+        }
+        self.suppressed.store(suppressed, Ordering::Relaxed);
+    }
+}
+
+#[macro_export]
+macro_rules! socd {
+    ($keys:expr, $policy:ident, [$($x:expr),+ $(,)?]) => {
+        $crate::processor::socd::Socd::new(
+            &$keys,
+            [$($x),+],
+            $crate::processor::socd::Policy::$policy,
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_winner() {
+        let held = [Some(30), None, Some(10)];
+        assert_eq!(winner(&held, Policy::Last), Some(2));
+        assert_eq!(winner(&held, Policy::First), Some(0));
+        assert_eq!(winner(&held, Policy::Neutral), None);
+
+        let held = [None, Some(10), None];
+        assert_eq!(winner(&held, Policy::Neutral), Some(1));
+        assert_eq!(winner(&[None, None], Policy::Last), None);
+    }
+}
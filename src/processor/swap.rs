@@ -0,0 +1,54 @@
+use itertools::izip;
+
+use crate::event::Event;
+use crate::handler::Handle;
+use crate::processor::{KeyProcessor, Process};
+use crate::state::State;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Swap {
+    On,
+    Off,
+    Toggle,
+    // Swap the next pressed key only.
+    OneShot,
+}
+
+// Looks up handlers like `keys`, but at the mirrored position `map[i]` while
+// hands are swapped. Use it in place of `keys`.
+pub struct SwapHands<const N: usize, const L: usize> {
+    keys: &'static KeyProcessor<N, L>,
+    map: [usize; N],
+}
+
+impl<const N: usize, const L: usize> SwapHands<N, L> {
+    pub const fn new(keys: &'static KeyProcessor<N, L>, map: [usize; N]) -> SwapHands<N, L> {
+        SwapHands { keys, map }
+    }
+}
+
+impl<const N: usize, const L: usize> Process<N, L> for SwapHands<N, L> {
+    fn process(
+        &self,
+        handlers: &mut [Option<&'static dyn Handle>; N],
+        events: &[Event; N],
+        state: &State,
+    ) {
+        if !state.is_swapped() {
+            return self.keys.process(handlers, events, state);
+        }
+
+        for (handler, event, i) in izip!(handlers, events, self.map) {
+            if matches!(event, Event::Press(_)) && handler.is_none() {
+                *handler = Some(self.keys.key(state.layer(), i));
+            }
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! swap {
+    ($keys:expr, [$($x:expr),* $(,)?]) => {
+        $crate::processor::swap::SwapHands::new(&$keys, [$($x),*])
+    };
+}
@@ -0,0 +1,201 @@
+use heapless::Vec;
+
+use crate::report::Report;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Macro {
+    Record(usize),
+    Play(usize),
+    Stop,
+}
+
+// A recording is a list of frames, each frame being the reports of one tick
+// followed by the number of ticks it lasted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item {
+    Report(Report),
+    Tick(u16),
+}
+
+// What happens when a recording does not fit in its slot.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    // Stop recording and keep everything captured so far.
+    #[default]
+    Truncate,
+    // Stop recording and clear the slot.
+    Discard,
+}
+
+pub struct Recorder<const S: usize, const M: usize> {
+    slots: [Vec<Item, M>; S],
+    overflow: Overflow,
+    recording: Option<usize>,
+    frame: usize,
+    playing: Option<(usize, usize, u16)>,
+}
+
+impl<const S: usize, const M: usize> Default for Recorder<S, M> {
+    fn default() -> Recorder<S, M> {
+        Recorder::new(Overflow::default())
+    }
+}
+
+impl<const S: usize, const M: usize> Recorder<S, M> {
+    pub fn new(overflow: Overflow) -> Recorder<S, M> {
+        Recorder {
+            slots: core::array::from_fn(|_| Vec::new()),
+            overflow,
+            recording: None,
+            frame: 0,
+            playing: None,
+        }
+    }
+
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    pub fn recording(&self) -> Option<usize> {
+        self.recording
+    }
+
+    pub fn run(&mut self, command: Macro) {
+        match command {
+            Macro::Record(slot) if self.recording == Some(slot) => self.stop(),
+            Macro::Record(slot) if slot < S => {
+                self.stop();
+                self.slots[slot].clear();
+                self.frame = 0;
+                self.recording = Some(slot);
+            }
+            Macro::Play(slot) if slot < S && self.recording != Some(slot) => {
+                self.playing = self.playing.or(Some((slot, 0, 0)));
+            }
+            Macro::Stop => self.stop(),
+            _ => {}
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(slot) = self.recording.take() {
+            // Drop the idle time before the recording was stopped.
+            let items = &mut self.slots[slot];
+            if items.len() == self.frame + 1 {
+                items.truncate(self.frame);
+            }
+        }
+    }
+
+    // Captures the reports sent in one tick.
+    pub fn record(&mut self, reports: &[Report]) {
+        let Some(slot) = self.recording else {
+            return;
+        };
+        let items = &mut self.slots[slot];
+        if items.is_empty() && reports.is_empty() {
+            return;
+        }
+
+        if let Some((Item::Tick(n), frame)) = items[self.frame..].split_last_mut() {
+            let same = frame.len() == reports.len()
+                && frame
+                    .iter()
+                    .zip(reports)
+                    .all(|(i, r)| *i == Item::Report(*r));
+            if same && *n < u16::MAX {
+                *n += 1;
+                return;
+            }
+        }
+
+        let frame = items.len();
+        let fits = reports
+            .iter()
+            .map(|report| Item::Report(*report))
+            .chain([Item::Tick(1)])
+            .all(|item| items.push(item).is_ok());
+        match (fits, self.overflow) {
+            (true, _) => self.frame = frame,
+            (false, Overflow::Truncate) => {
+                items.truncate(frame);
+                self.recording = None;
+            }
+            (false, Overflow::Discard) => {
+                items.clear();
+                self.recording = None;
+            }
+        }
+    }
+
+    // Reports of the recording being played back for this tick.
+    pub fn play(&mut self) -> Option<&[Item]> {
+        let (slot, start, tick) = self.playing?;
+        let items = &self.slots[slot];
+        let Some(end) = items[start..]
+            .iter()
+            .position(|i| matches!(i, Item::Tick(_)))
+        else {
+            self.playing = None;
+            return None;
+        };
+        let end = start + end;
+
+        self.playing = match items[end] {
+            Item::Tick(n) if tick + 1 < n => Some((slot, start, tick + 1)),
+            _ if end + 1 < items.len() => Some((slot, end + 1, 0)),
+            _ => None,
+        };
+        Some(&items[start..end])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::report::Keyboard;
+
+    const A: Report = Report::Keyboard(Keyboard::A);
+    const B: Report = Report::Keyboard(Keyboard::B);
+
+    #[test]
+    fn record() {
+        let mut recorder = Recorder::<2, 8>::default();
+        recorder.run(Macro::Record(0));
+        recorder.record(&[]);
+        recorder.record(&[A]);
+        recorder.record(&[A]);
+        recorder.record(&[A, B]);
+        recorder.record(&[]);
+        recorder.record(&[]);
+        recorder.run(Macro::Record(0));
+        assert_eq!(recorder.recording(), None);
+
+        recorder.run(Macro::Play(0));
+        let a = [Item::Report(A)];
+        let ab = [Item::Report(A), Item::Report(B)];
+        assert_eq!(recorder.play(), Some(&a[..]));
+        assert_eq!(recorder.play(), Some(&a[..]));
+        assert_eq!(recorder.play(), Some(&ab[..]));
+        assert_eq!(recorder.play(), None);
+    }
+
+    #[test]
+    fn overflow() {
+        let mut recorder = Recorder::<2, 4>::new(Overflow::Truncate);
+        recorder.run(Macro::Record(1));
+        recorder.record(&[A]);
+        recorder.record(&[B]);
+        recorder.record(&[A, B]);
+        assert_eq!(recorder.recording(), None);
+        assert_eq!(recorder.slots[1].len(), 4);
+
+        recorder.set_overflow(Overflow::Discard);
+        recorder.run(Macro::Record(1));
+        recorder.record(&[A]);
+        recorder.record(&[B]);
+        recorder.record(&[A, B]);
+        assert_eq!(recorder.recording(), None);
+        assert!(recorder.slots[1].is_empty());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::key_override::{self, KeyOverride};
+use crate::keymap::Reports;
+use crate::report::{Keyboard, Modifiers, Report};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    // Resend the last key.
+    Last,
+    // Send the counterpart of the last key, e.g. Up after Down.
+    Alternate,
+}
+
+// Remembers the last key sent and replays it, or its counterpart, on demand.
+// Counterparts are key overrides applied to the last key and its modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repeater {
+    alternates: &'static [KeyOverride],
+    last: Option<(Keyboard, Modifiers)>,
+    held: [u32; 8],
+    repeat: Option<Repeat>,
+}
+
+impl Default for Repeater {
+    fn default() -> Repeater {
+        Repeater::new(&[])
+    }
+}
+
+impl Repeater {
+    pub const fn new(alternates: &'static [KeyOverride]) -> Repeater {
+        Repeater {
+            alternates,
+            last: None,
+            held: [0; 8],
+            repeat: None,
+        }
+    }
+
+    pub fn set_alternates(&mut self, alternates: &'static [KeyOverride]) {
+        self.alternates = alternates;
+    }
+
+    pub fn last(&self) -> Option<(Keyboard, Modifiers)> {
+        self.last
+    }
+
+    pub fn press(&mut self, repeat: Repeat) {
+        self.repeat = self.repeat.or(Some(repeat));
+    }
+
+    // Remembers the newest key in this tick's reports and adds the repeated
+    // key, if any.
+    pub fn update(&mut self, reports: &mut Reports) {
+        let modifiers = Modifiers::from_reports(reports);
+        let mut held = [0; 8];
+        for report in reports.iter() {
+            let Report::Keyboard(key) = report else {
+                continue;
+            };
+            let code = *key as u8;
+            if code < Keyboard::A as u8 || !Modifiers::from_key(*key).is_empty() {
+                continue;
+            }
+            let (i, bit) = (code as usize / 32, 1 << (code % 32));
+            if self.held[i] & bit == 0 {
+                self.last = Some((*key, modifiers));
+            }
+            held[i] |= bit;
+        }
+        self.held = held;
+
+        let Some((key, modifiers)) = self.last else {
+            self.repeat = None;
+            return;
+        };
+        let mut repeated: Reports = modifiers.keys().map(Report::Keyboard).collect();
+        repeated.push(Report::Keyboard(key)).unwrap();
+        match self.repeat.take() {
+            Some(Repeat::Last) => {}
+            Some(Repeat::Alternate) if self.alternate(key, modifiers) => {
+                key_override::apply(self.alternates, &mut repeated)
+            }
+            _ => return,
+        }
+        for report in repeated {
+            if !reports.contains(&report) {
+                reports.push(report).unwrap();
+            }
+        }
+    }
+
+    fn alternate(&self, key: Keyboard, modifiers: Modifiers) -> bool {
+        self.alternates.iter().any(|o| o.matches(key, modifiers))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ko;
+
+    static ALTERNATES: [KeyOverride; 2] = [ko!(NONE, DownArrow, UpArrow), ko!(CTL, Z, LCTL, Y)];
+
+    fn reports(keys: &[Keyboard]) -> Reports {
+        keys.iter().map(|key| Report::Keyboard(*key)).collect()
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut repeater = Repeater::new(&ALTERNATES);
+        let mut output = reports(&[Keyboard::LeftShift, Keyboard::DownArrow]);
+        repeater.update(&mut output);
+        let mut output = reports(&[]);
+        repeater.update(&mut output);
+
+        repeater.press(Repeat::Last);
+        let mut output = reports(&[]);
+        repeater.update(&mut output);
+        assert_eq!(output, reports(&[Keyboard::LeftShift, Keyboard::DownArrow]));
+
+        repeater.press(Repeat::Alternate);
+        let mut output = reports(&[]);
+        repeater.update(&mut output);
+        assert_eq!(output, reports(&[Keyboard::LeftShift, Keyboard::UpArrow]));
+
+        let mut output = reports(&[Keyboard::RightControl, Keyboard::Z]);
+        repeater.update(&mut output);
+        repeater.press(Repeat::Alternate);
+        let mut output = reports(&[]);
+        repeater.update(&mut output);
+        assert_eq!(output, reports(&[Keyboard::Y, Keyboard::LeftControl]));
+
+        let mut output = reports(&[Keyboard::X]);
+        repeater.update(&mut output);
+        repeater.press(Repeat::Alternate);
+        let mut output = reports(&[]);
+        repeater.update(&mut output);
+        assert!(output.is_empty());
+    }
+}
@@ -1,3 +1,6 @@
+pub use usbd_human_interface_device::device::joystick::JoystickReport;
+pub use usbd_human_interface_device::device::keyboard::KeyboardLedsReport;
+pub use usbd_human_interface_device::device::mouse::WheelMouseReport;
 pub use usbd_human_interface_device::page::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -5,5 +8,92 @@ pub enum Report {
     Keyboard(Keyboard),
     Consumer(Consumer),
     Desktop(Desktop),
+    Mouse(WheelMouseReport),
+    Gamepad(JoystickReport),
     Custom(u8),
 }
+
+// Modifier keys as in the modifier byte of a keyboard report.
+// `CTL`, `SFT`, `ALT` and `GUI` stand for either side.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const LCTL: Modifiers = Modifiers(1 << 0);
+    pub const LSFT: Modifiers = Modifiers(1 << 1);
+    pub const LALT: Modifiers = Modifiers(1 << 2);
+    pub const LGUI: Modifiers = Modifiers(1 << 3);
+    pub const RCTL: Modifiers = Modifiers(1 << 4);
+    pub const RSFT: Modifiers = Modifiers(1 << 5);
+    pub const RALT: Modifiers = Modifiers(1 << 6);
+    pub const RGUI: Modifiers = Modifiers(1 << 7);
+    pub const CTL: Modifiers = Modifiers::LCTL.union(Modifiers::RCTL);
+    pub const SFT: Modifiers = Modifiers::LSFT.union(Modifiers::RSFT);
+    pub const ALT: Modifiers = Modifiers::LALT.union(Modifiers::RALT);
+    pub const GUI: Modifiers = Modifiers::LGUI.union(Modifiers::RGUI);
+
+    pub const fn from_bits(bits: u8) -> Modifiers {
+        Modifiers(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn union(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+
+    pub const fn intersection(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 & other.0)
+    }
+
+    pub const fn difference(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 & !other.0)
+    }
+
+    pub const fn intersects(self, other: Modifiers) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn from_key(key: Keyboard) -> Modifiers {
+        match key as u8 {
+            code @ 0xE0..=0xE7 => Modifiers(1 << (code - 0xE0)),
+            _ => Modifiers::NONE,
+        }
+    }
+
+    pub fn from_reports(reports: &[Report]) -> Modifiers {
+        reports
+            .iter()
+            .fold(Modifiers::NONE, |modifiers, report| match report {
+                Report::Keyboard(key) => modifiers.union(Modifiers::from_key(*key)),
+                _ => modifiers,
+            })
+    }
+
+    // Both sides of every modifier in `self`.
+    pub const fn sides(self) -> Modifiers {
+        let kinds = (self.0 | self.0 >> 4) & 0x0F;
+        Modifiers(kinds | kinds << 4)
+    }
+
+    // Whether every modifier in `other` is held: on its side, or on either
+    // side for those given with both sides such as `SFT`.
+    pub const fn matches(self, other: Modifiers) -> bool {
+        let either = other.0 & other.0 >> 4 & 0x0F;
+        let exact = other.0 & !(either | either << 4);
+        self.0 & exact == exact && self.sides().0 & either == either
+    }
+
+    pub fn keys(self) -> impl Iterator<Item = Keyboard> {
+        (0..8)
+            .filter(move |i| self.0 & (1 << i) != 0)
+            .map(|i| Keyboard::from(0xE0 + i))
+    }
+}
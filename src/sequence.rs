@@ -0,0 +1,87 @@
+use heapless::Vec;
+
+use crate::report::Keyboard;
+
+const MAX_FRAMES: usize = 24;
+// All eight modifiers and a key.
+const MAX_KEYS: usize = 9;
+
+pub type Frame = Vec<Keyboard, MAX_KEYS>;
+
+// Keys to be sent over several ticks, each frame is held for `hold` ticks.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    frames: Vec<Frame, MAX_FRAMES>,
+    hold: usize,
+    tick: usize,
+}
+
+impl Sequence {
+    pub fn new(hold: usize) -> Sequence {
+        Sequence {
+            hold,
+            ..Default::default()
+        }
+    }
+
+    // Keys past `MAX_KEYS` are dropped.
+    pub fn push(&mut self, keys: &[Keyboard]) {
+        let frame = keys.iter().copied().take(MAX_KEYS).collect();
+        self.frames.push(frame).unwrap();
+    }
+
+    // Presses `keys` on top of `held`, then releases them.
+    pub fn tap(&mut self, held: &[Keyboard], keys: &[Keyboard]) {
+        let frame: Frame = held.iter().chain(keys).copied().take(MAX_KEYS).collect();
+        self.push(&frame);
+        self.push(held);
+    }
+
+    // Whether `n` more frames can be pushed.
+    pub fn fits(&self, n: usize) -> bool {
+        self.frames.len() + n <= MAX_FRAMES
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.tick / self.hold.max(1) < self.frames.len()
+    }
+
+    pub fn play(&mut self) -> Option<&Frame> {
+        let frame = self.frames.get(self.tick / self.hold.max(1))?;
+        self.tick += 1;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn play() {
+        let mut sequence = Sequence::new(2);
+        sequence.tap(&[Keyboard::LeftShift], &[Keyboard::A]);
+        assert!(sequence.is_playing());
+
+        let frames = [
+            &[Keyboard::LeftShift, Keyboard::A][..],
+            &[Keyboard::LeftShift, Keyboard::A][..],
+            &[Keyboard::LeftShift][..],
+            &[Keyboard::LeftShift][..],
+        ];
+        for frame in frames {
+            assert_eq!(sequence.play().unwrap().as_slice(), frame);
+        }
+        assert_eq!(sequence.play(), None);
+        assert!(!sequence.is_playing());
+
+        // All modifiers held, and more keys than fit
+        let modifiers: Frame = (0xE0..=0xE7).map(Keyboard::from).collect();
+        let mut sequence = Sequence::new(1);
+        sequence.tap(&modifiers, &[Keyboard::A, Keyboard::B]);
+        let pressed = sequence.play().unwrap();
+        assert_eq!(pressed[..8], modifiers[..]);
+        assert_eq!(pressed[8..], [Keyboard::A]);
+        assert_eq!(sequence.play(), Some(&modifiers));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::report::Keyboard;
+use crate::sequence::Sequence;
+
+// Step by which the tapping term is raised or lowered.
+const STEP: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tune {
+    // Raise the tapping term.
+    Up,
+    // Lower the tapping term.
+    Down,
+    // Type out the tapping term.
+    Print,
+}
+
+// Timing parameters of a keymap that can be changed at runtime, in ticks.
+// Hold-taps without their own threshold use the tapping term, chords
+// need both keys pressed within the combo term, and the keymap debounces
+// switches for `debounce` ticks. A tuned tapping term only takes effect
+// once no key is held, so a held key keeps the term it was pressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    tapping_term: usize,
+    next_term: usize,
+    combo_term: usize,
+    debounce: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings::new(200, 50, 5)
+    }
+}
+
+impl Settings {
+    pub const fn new(tapping_term: usize, combo_term: usize, debounce: usize) -> Settings {
+        Settings {
+            tapping_term,
+            next_term: tapping_term,
+            combo_term,
+            debounce,
+        }
+    }
+
+    pub fn tapping_term(&self) -> usize {
+        self.tapping_term
+    }
+
+    pub fn set_tapping_term(&mut self, term: usize) {
+        self.tapping_term = term;
+        self.next_term = term;
+    }
+
+    // Applies the tuned tapping term, when no key is held.
+    pub fn settle(&mut self) {
+        self.tapping_term = self.next_term;
+    }
+
+    pub fn combo_term(&self) -> usize {
+        self.combo_term
+    }
+
+    pub fn set_combo_term(&mut self, term: usize) {
+        self.combo_term = term;
+    }
+
+    pub fn debounce(&self) -> usize {
+        self.debounce
+    }
+
+    pub fn set_debounce(&mut self, debounce: usize) {
+        self.debounce = debounce;
+    }
+
+    // Returns the tuned tapping term when it should be typed out.
+    pub fn tune(&mut self, tune: Tune) -> Option<usize> {
+        self.next_term = match tune {
+            Tune::Up => self.next_term.saturating_add(STEP),
+            Tune::Down => self.next_term.saturating_sub(STEP).max(STEP),
+            Tune::Print => return Some(self.next_term),
+        };
+        None
+    }
+}
+
+// Types out `value` in decimal.
+pub fn print(sequence: &mut Sequence, value: usize) {
+    let digits = value.checked_ilog10().unwrap_or(0) + 1;
+    for n in (0..digits).rev() {
+        let key = match value / 10usize.pow(n) % 10 {
+            0 => Keyboard::Keyboard0,
+            digit => Keyboard::from(Keyboard::Keyboard1 as u8 + digit as u8 - 1),
+        };
+        if sequence.fits(2) {
+            sequence.tap(&[], &[key]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tune() {
+        let mut settings = Settings::new(10, 50, 5);
+        assert_eq!(settings.tune(Tune::Up), None);
+        assert_eq!(settings.tune(Tune::Print), Some(15));
+        assert_eq!(settings.tapping_term(), 10);
+        (0..5).for_each(|_| {
+            settings.tune(Tune::Down);
+        });
+        settings.settle();
+        assert_eq!(settings.tapping_term(), 5);
+
+        let mut sequence = Sequence::new(1);
+        print(&mut sequence, 205);
+        let keys = [
+            Keyboard::Keyboard2,
+            Keyboard::Keyboard0,
+            Keyboard::Keyboard5,
+        ];
+        for key in keys {
+            assert_eq!(sequence.play().unwrap().as_slice(), [key]);
+            assert!(sequence.play().unwrap().is_empty());
+        }
+        assert_eq!(sequence.play(), None);
+    }
+}
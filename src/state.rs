@@ -0,0 +1,193 @@
+use crate::mode::Modes;
+use crate::processor::swap::Swap;
+use crate::report::{Keyboard, KeyboardLedsReport, Leds, Modifiers};
+use crate::settings::Settings;
+use crate::unicode;
+
+// Layers are bits of a u32, so keymaps have at most this many.
+pub const MAX_LAYERS: usize = u32::BITS as usize;
+
+// Bit of `layer`, none for layers past MAX_LAYERS.
+fn bit(layer: usize) -> u32 {
+    u32::try_from(layer)
+        .ok()
+        .and_then(|layer| 1u32.checked_shl(layer))
+        .unwrap_or(0)
+}
+
+// Keymap state shared with actions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct State {
+    layers: u32,
+    locked: u32,
+    default: usize,
+    modifiers: Modifiers,
+    leds: KeyboardLedsReport,
+    unicode: unicode::Mode,
+    modes: Modes,
+    swapped: bool,
+    swap_once: bool,
+    unshifted: &'static [Keyboard],
+    ticks: usize,
+    settings: Settings,
+}
+
+impl State {
+    pub fn new() -> State {
+        Default::default()
+    }
+
+    // Ticks since the keymap started, wrapping around.
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    // Highest active layer, the default layer being always active.
+    pub fn layer(&self) -> usize {
+        let layers = self.layers | bit(self.default);
+        (u32::BITS - layers.leading_zeros()) as usize - 1
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn is_active(&self, layer: usize) -> bool {
+        layer == self.default || self.layers & bit(layer) != 0
+    }
+
+    pub fn is_locked(&self, layer: usize) -> bool {
+        self.locked & bit(layer) != 0
+    }
+
+    pub fn default_layer(&self) -> usize {
+        self.default
+    }
+
+    pub fn set_default_layer(&mut self, layer: usize) {
+        if layer < MAX_LAYERS {
+            self.default = layer;
+        }
+    }
+
+    // Makes `layer` the only active layer besides the default one.
+    pub fn set_layer(&mut self, layer: usize) {
+        if layer >= MAX_LAYERS {
+            return;
+        }
+        self.layers = bit(layer);
+        self.locked &= self.layers;
+    }
+
+    pub fn layer_on(&mut self, layer: usize) {
+        self.layers |= bit(layer);
+    }
+
+    pub fn layer_toggle(&mut self, layer: usize) {
+        match self.is_active(layer) {
+            true => self.layer_off(layer),
+            false => self.layer_on(layer),
+        }
+    }
+
+    // Locked layers stay on until they are unlocked.
+    pub fn layer_off(&mut self, layer: usize) {
+        if !self.is_locked(layer) {
+            self.layers &= !bit(layer);
+        }
+    }
+
+    // Locks `layer` on, or unlocks and turns it off if already locked.
+    pub fn lock_layer(&mut self, layer: usize) {
+        self.locked ^= bit(layer);
+        self.layer_off(layer);
+    }
+
+    // Modifiers held in the last tick.
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.modifiers = modifiers;
+    }
+
+    // LED states last sent by the host.
+    pub fn leds(&self) -> KeyboardLedsReport {
+        self.leds
+    }
+
+    pub fn set_leds(&mut self, leds: KeyboardLedsReport) {
+        self.leds = leds;
+    }
+
+    pub fn is_lit(&self, led: Leds) -> bool {
+        match led {
+            Leds::NumLock => self.leds.num_lock,
+            Leds::CapsLock => self.leds.caps_lock,
+            Leds::ScrollLock => self.leds.scroll_lock,
+            Leds::Compose => self.leds.compose,
+            Leds::Kana => self.leds.kana,
+            _ => false,
+        }
+    }
+
+    pub fn unicode(&self) -> unicode::Mode {
+        self.unicode
+    }
+
+    pub fn set_unicode(&mut self, mode: unicode::Mode) {
+        self.unicode = mode;
+    }
+
+    pub fn modes(&self) -> Modes {
+        self.modes
+    }
+
+    pub fn set_modes(&mut self, modes: Modes) {
+        self.modes = modes;
+    }
+
+    pub fn toggle(&mut self, modes: Modes) {
+        self.modes = self.modes.toggle(modes);
+    }
+
+    pub fn is_swapped(&self) -> bool {
+        self.swapped || self.swap_once
+    }
+
+    pub fn swap(&mut self, swap: Swap) {
+        match swap {
+            Swap::On => self.swapped = true,
+            Swap::Off => self.swapped = false,
+            Swap::Toggle => self.swapped = !self.swapped,
+            Swap::OneShot => self.swap_once = true,
+        }
+    }
+
+    // Ends a one-shot swap once a key was pressed.
+    pub fn swap_used(&mut self) {
+        self.swap_once = false;
+    }
+
+    // Keys that auto-shift leaves alone.
+    pub fn is_unshifted(&self, key: Keyboard) -> bool {
+        self.unshifted.contains(&key)
+    }
+
+    pub fn set_unshifted(&mut self, keys: &'static [Keyboard]) {
+        self.unshifted = keys;
+    }
+}
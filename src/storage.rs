@@ -0,0 +1,550 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+use embedded_storage::nor_flash::NorFlash;
+
+use crate::mode::Modes;
+use crate::processor::dynamic::{self, DynamicKeymap};
+use crate::state::{State, MAX_LAYERS};
+use crate::unicode;
+
+// Bank header: magic and generation, the newest bank being the active one.
+const MAGIC: [u8; 4] = *b"RKST";
+const BANK_HEADER: usize = 8;
+// Record header: kind, version, data length and CRC of all of them.
+const HEADER: usize = 8;
+// Flash is read and written through a buffer of this size.
+const CHUNK: usize = 32;
+const ERASED: u8 = 0xFF;
+const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+// Current version of each record.
+const KEYMAP_VERSION: u8 = 1;
+const DEFAULT_LAYER_VERSION: u8 = 1;
+const TUNABLES_VERSION: u8 = 2;
+// Largest of the small records.
+const SMALL: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Record {
+    Keymap = 0,
+    DefaultLayer = 1,
+    Tunables = 2,
+}
+
+const RECORDS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    Flash(E),
+    // The record does not fit in a bank, or in the buffer it is read into.
+    Length,
+    Keymap(dynamic::Error),
+}
+
+struct Entry {
+    kind: u8,
+    version: u8,
+    len: usize,
+    crc: u32,
+}
+
+// Settings saved in flash as a log of records, in two banks of whole erase
+// blocks starting at `start`. Records are appended to the active bank and
+// the latest valid one of each kind is used. When the active bank is full,
+// the latest records are copied to the other bank, which then takes over,
+// so a power loss at any time leaves one of them intact.
+pub struct Storage<F: NorFlash> {
+    flash: F,
+    start: u32,
+    size: u32,
+    bank: u32,
+    generation: u32,
+    head: u32,
+    latest: [Option<u32>; RECORDS],
+}
+
+impl<F: NorFlash> Storage<F> {
+    // The read and write sizes of the flash must divide `CHUNK`.
+    pub fn new(flash: F, start: u32, size: u32) -> Result<Storage<F>, Error<F::Error>> {
+        assert!(CHUNK.is_multiple_of(F::READ_SIZE) && CHUNK.is_multiple_of(F::WRITE_SIZE));
+        let erase = F::ERASE_SIZE as u32;
+        let mut storage = Storage {
+            flash,
+            start,
+            size: size / 2 / erase * erase,
+            bank: 0,
+            generation: 0,
+            head: 0,
+            latest: [None; RECORDS],
+        };
+        (storage.bank, storage.generation) =
+            match (storage.bank_generation(0)?, storage.bank_generation(1)?) {
+                (Some(a), Some(b)) if b > a => (1, b),
+                (Some(a), _) => (0, a),
+                (None, Some(b)) => (1, b),
+                (None, None) => {
+                    storage.erase(0)?;
+                    storage.mark(0, 1)?;
+                    (0, 1)
+                }
+            };
+        storage.scan()?;
+        Ok(storage)
+    }
+
+    fn align(len: usize) -> usize {
+        let align = F::READ_SIZE.max(F::WRITE_SIZE);
+        len.div_ceil(align) * align
+    }
+
+    fn address(&self, bank: u32, at: u32) -> u32 {
+        self.start + bank * self.size + at
+    }
+
+    // Calls `f` with the `len` bytes following the first `skip` ones at
+    // `at`, a chunk at a time.
+    fn stream(
+        &mut self,
+        bank: u32,
+        at: u32,
+        skip: usize,
+        len: usize,
+        mut f: impl FnMut(&[u8]),
+    ) -> Result<(), Error<F::Error>> {
+        let total = skip + len;
+        let mut buffer = [0; CHUNK];
+        let mut done = 0;
+        while done < total {
+            let n = CHUNK.min(Self::align(total - done));
+            let address = self.address(bank, at + done as u32);
+            self.flash
+                .read(address, &mut buffer[..n])
+                .map_err(Error::Flash)?;
+            let chunk = &buffer[..n.min(total - done)];
+            f(&chunk[skip.saturating_sub(done).min(chunk.len())..]);
+            done += n;
+        }
+        Ok(())
+    }
+
+    // Writes `bytes` at `at`, padded with erased bytes to the alignment.
+    fn program<'a>(
+        &mut self,
+        bank: u32,
+        mut at: u32,
+        bytes: impl Iterator<Item = &'a u8>,
+    ) -> Result<u32, Error<F::Error>> {
+        let mut buffer = [ERASED; CHUNK];
+        let mut n = 0;
+        for byte in bytes {
+            buffer[n] = *byte;
+            n += 1;
+            if n == CHUNK {
+                at = self.write_chunk(bank, at, &buffer)?;
+                (buffer, n) = ([ERASED; CHUNK], 0);
+            }
+        }
+        if n > 0 {
+            at = self.write_chunk(bank, at, &buffer[..Self::align(n)])?;
+        }
+        Ok(at)
+    }
+
+    fn write_chunk(&mut self, bank: u32, at: u32, bytes: &[u8]) -> Result<u32, Error<F::Error>> {
+        let address = self.address(bank, at);
+        self.flash.write(address, bytes).map_err(Error::Flash)?;
+        Ok(at + bytes.len() as u32)
+    }
+
+    fn bank_generation(&mut self, bank: u32) -> Result<Option<u32>, Error<F::Error>> {
+        let mut header = [0; BANK_HEADER];
+        self.stream(bank, 0, 0, BANK_HEADER, |chunk| {
+            header.copy_from_slice(chunk)
+        })?;
+        let generation = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        Ok((header[..4] == MAGIC).then_some(generation))
+    }
+
+    fn erase(&mut self, bank: u32) -> Result<(), Error<F::Error>> {
+        let (from, to) = (self.address(bank, 0), self.address(bank, self.size));
+        self.flash.erase(from, to).map_err(Error::Flash)
+    }
+
+    fn mark(&mut self, bank: u32, generation: u32) -> Result<(), Error<F::Error>> {
+        self.program(bank, 0, MAGIC.iter().chain(&generation.to_le_bytes()))?;
+        Ok(())
+    }
+
+    // Header of the record at `at`, if there is one.
+    fn entry(&mut self, bank: u32, at: u32) -> Result<Option<Entry>, Error<F::Error>> {
+        if at as usize + Self::align(HEADER) > self.size as usize {
+            return Ok(None);
+        }
+        let mut header = [0; HEADER];
+        self.stream(bank, at, 0, HEADER, |chunk| header.copy_from_slice(chunk))?;
+        if header == [ERASED; HEADER] {
+            return Ok(None);
+        }
+        Ok(Some(Entry {
+            kind: header[0],
+            version: header[1],
+            len: u16::from_le_bytes([header[2], header[3]]) as usize,
+            crc: u32::from_le_bytes([header[4], header[5], header[6], header[7]]),
+        }))
+    }
+
+    fn checksum(kind: u8, version: u8, data: &[u8]) -> u32 {
+        let mut digest = CRC.digest();
+        digest.update(&[kind, version]);
+        digest.update(&(data.len() as u16).to_le_bytes());
+        digest.update(data);
+        digest.finalize()
+    }
+
+    fn is_valid(&mut self, at: u32, entry: &Entry) -> Result<bool, Error<F::Error>> {
+        let mut digest = CRC.digest();
+        digest.update(&[entry.kind, entry.version]);
+        digest.update(&(entry.len as u16).to_le_bytes());
+        self.stream(self.bank, at, HEADER, entry.len, |chunk| {
+            digest.update(chunk)
+        })?;
+        Ok(digest.finalize() == entry.crc)
+    }
+
+    // Finds the latest valid records and the end of the log.
+    fn scan(&mut self) -> Result<(), Error<F::Error>> {
+        let mut at = Self::align(BANK_HEADER) as u32;
+        self.latest = [None; RECORDS];
+        while let Some(entry) = self.entry(self.bank, at)? {
+            let end = at + Self::align(HEADER + entry.len) as u32;
+            if end > self.size {
+                // A torn header, nothing can be written after it.
+                at = self.size;
+                break;
+            }
+            if (entry.kind as usize) < RECORDS && self.is_valid(at, &entry)? {
+                self.latest[entry.kind as usize] = Some(at);
+            }
+            at = end;
+        }
+        self.head = at;
+        Ok(())
+    }
+
+    // Copies the latest records to the other bank and makes it active.
+    fn compact(&mut self) -> Result<(), Error<F::Error>> {
+        let (from, to) = (self.bank, 1 - self.bank);
+        self.erase(to)?;
+
+        let mut head = Self::align(BANK_HEADER) as u32;
+        let mut latest = [None; RECORDS];
+        for (kind, at) in self.latest.into_iter().enumerate() {
+            let Some(at) = at else {
+                continue;
+            };
+            let Some(entry) = self.entry(from, at)? else {
+                continue;
+            };
+            let len = Self::align(HEADER + entry.len) as u32;
+            let mut buffer = [0; CHUNK];
+            for offset in (0..len).step_by(CHUNK) {
+                let n = CHUNK.min((len - offset) as usize);
+                let address = self.address(from, at + offset);
+                self.flash
+                    .read(address, &mut buffer[..n])
+                    .map_err(Error::Flash)?;
+                self.write_chunk(to, head + offset, &buffer[..n])?;
+            }
+            latest[kind] = Some(head);
+            head += len;
+        }
+
+        // The bank is only valid once everything was copied.
+        let generation = self.generation.wrapping_add(1);
+        self.mark(to, generation)?;
+        (self.bank, self.generation, self.head, self.latest) = (to, generation, head, latest);
+        Ok(())
+    }
+
+    pub fn write(
+        &mut self,
+        record: Record,
+        version: u8,
+        data: &[u8],
+    ) -> Result<(), Error<F::Error>> {
+        let len = u16::try_from(data.len()).map_err(|_| Error::Length)?;
+        let size = Self::align(HEADER + data.len()) as u32;
+        if self.head + size > self.size {
+            self.compact()?;
+        }
+        if self.head + size > self.size {
+            return Err(Error::Length);
+        }
+
+        let kind = record as u8;
+        let mut header = [kind, version, 0, 0, 0, 0, 0, 0];
+        header[2..4].copy_from_slice(&len.to_le_bytes());
+        header[4..].copy_from_slice(&Self::checksum(kind, version, data).to_le_bytes());
+        let at = self.head;
+        self.head = self.program(self.bank, at, header.iter().chain(data))?;
+        self.latest[kind as usize] = Some(at);
+        Ok(())
+    }
+
+    // Reads the latest record of a kind, returning its version and length.
+    pub fn read(
+        &mut self,
+        record: Record,
+        bytes: &mut [u8],
+    ) -> Result<Option<(u8, usize)>, Error<F::Error>> {
+        let Some(at) = self.latest[record as usize] else {
+            return Ok(None);
+        };
+        let Some(entry) = self.entry(self.bank, at)? else {
+            return Ok(None);
+        };
+        let data = bytes.get_mut(..entry.len).ok_or(Error::Length)?;
+        let mut n = 0;
+        self.stream(self.bank, at, HEADER, entry.len, |chunk| {
+            data[n..n + chunk.len()].copy_from_slice(chunk);
+            n += chunk.len();
+        })?;
+        Ok(Some((entry.version, entry.len)))
+    }
+
+    // Writes a record only if it changed, to spare the flash.
+    fn update(&mut self, record: Record, version: u8, data: &[u8]) -> Result<(), Error<F::Error>> {
+        let mut bytes = [0; SMALL];
+        let saved = match self.read(record, &mut bytes) {
+            Err(Error::Length) => None,
+            result => result?,
+        };
+        match saved == Some((version, data.len())) && bytes[..data.len()] == *data {
+            true => Ok(()),
+            false => self.write(record, version, data),
+        }
+    }
+
+    // `bytes` must hold `DynamicKeymap::<N, L>::SIZE` bytes.
+    pub fn save_keymap<const N: usize, const L: usize>(
+        &mut self,
+        keymap: &DynamicKeymap<N, L>,
+        bytes: &mut [u8],
+    ) -> Result<(), Error<F::Error>> {
+        let len = keymap.save(bytes).map_err(Error::Keymap)?;
+        self.write(Record::Keymap, KEYMAP_VERSION, &bytes[..len])
+    }
+
+    // Returns whether a saved keymap was loaded.
+    pub fn load_keymap<const N: usize, const L: usize>(
+        &mut self,
+        keymap: &DynamicKeymap<N, L>,
+        bytes: &mut [u8],
+    ) -> Result<bool, Error<F::Error>> {
+        match self.read(Record::Keymap, bytes)? {
+            Some((KEYMAP_VERSION, len)) => keymap.load(&bytes[..len]).map_err(Error::Keymap)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn save_state(&mut self, state: &State) -> Result<(), Error<F::Error>> {
+        let layer = [state.default_layer() as u8];
+        self.update(Record::DefaultLayer, DEFAULT_LAYER_VERSION, &layer)?;
+        self.update(Record::Tunables, TUNABLES_VERSION, &tunables(state))
+    }
+
+    // Restores the default layer and tunables, keeping the current ones
+    // for what was not saved. A default layer past the `layers` of the
+    // keymap, e.g. saved by firmware with more layers, is not restored.
+    pub fn load_state(&mut self, state: &mut State, layers: usize) -> Result<(), Error<F::Error>> {
+        let mut bytes = [0; SMALL];
+        if let Some((DEFAULT_LAYER_VERSION, 1)) = self.read(Record::DefaultLayer, &mut bytes)? {
+            if (bytes[0] as usize) < layers.min(MAX_LAYERS) {
+                state.set_default_layer(bytes[0] as usize);
+            }
+        }
+        if let Some((version, len)) = self.read(Record::Tunables, &mut bytes)? {
+            restore_tunables(version, &bytes[..len], state);
+        }
+        Ok(())
+    }
+}
+
+fn tunables(state: &State) -> [u8; 8] {
+    let settings = state.settings();
+    let [m0, m1] = state.modes().bits().to_le_bytes();
+    let term = |term: usize| (term.min(u16::MAX as usize) as u16).to_le_bytes();
+    let [t0, t1] = term(settings.tapping_term());
+    let [c0, c1] = term(settings.combo_term());
+    let debounce = settings.debounce().min(u8::MAX as usize) as u8;
+    [m0, m1, state.unicode() as u8, t0, t1, c0, c1, debounce]
+}
+
+// Reads tunables of any version, migrating older ones.
+// Version 1 had no timings, which are then left as they are.
+fn restore_tunables(version: u8, bytes: &[u8], state: &mut State) {
+    let (m0, m1, mode) = match (version, bytes) {
+        (1, [m0, m1, mode]) => (*m0, *m1, *mode),
+        (2, [m0, m1, mode, t0, t1, c0, c1, debounce]) => {
+            let settings = state.settings_mut();
+            settings.set_tapping_term(u16::from_le_bytes([*t0, *t1]) as usize);
+            settings.set_combo_term(u16::from_le_bytes([*c0, *c1]) as usize);
+            settings.set_debounce(*debounce as usize);
+            (*m0, *m1, *mode)
+        }
+        _ => return,
+    };
+    state.set_modes(Modes::from_bits(u16::from_le_bytes([m0, m1])));
+    state.set_unicode(match mode {
+        1 => unicode::Mode::Windows,
+        2 => unicode::Mode::WinCompose,
+        3 => unicode::Mode::MacOS,
+        _ => unicode::Mode::Linux,
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use embedded_storage::nor_flash::{
+        check_erase, check_read, check_write, ErrorType, NorFlashErrorKind, ReadNorFlash,
+    };
+
+    use super::*;
+
+    struct Flash([u8; 1024]);
+
+    impl ErrorType for Flash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for Flash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            check_read(self, offset, bytes.len())?;
+            bytes.copy_from_slice(&self.0[offset as usize..][..bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl NorFlash for Flash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 128;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            check_erase(self, from, to)?;
+            self.0[from as usize..to as usize].fill(ERASED);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            check_write(self, offset, bytes.len())?;
+            for (old, new) in self.0[offset as usize..].iter_mut().zip(bytes) {
+                // Writing can only clear bits.
+                assert_eq!(*old & new, *new);
+                *old = *new;
+            }
+            Ok(())
+        }
+    }
+
+    static DEFAULTS: [[u16; 3]; 2] = [[0x04, 0x05, 0x5221], [0x01, 0x01, 0x06]];
+    static KEYMAP: DynamicKeymap<3, 2> = DynamicKeymap::new(&DEFAULTS);
+
+    #[test]
+    fn test_storage() {
+        let mut flash = Flash([0; 1024]);
+        let mut bytes = [0; DynamicKeymap::<3, 2>::SIZE];
+        let mut storage = Storage::new(&mut flash, 0, 1024).unwrap();
+        let mut state = State::new();
+        storage.load_state(&mut state, 4).unwrap();
+        assert_eq!(state, State::new());
+        assert_eq!(storage.load_keymap(&KEYMAP, &mut bytes), Ok(false));
+
+        state.set_default_layer(1);
+        state.set_modes(Modes::GAMING);
+        state.set_unicode(unicode::Mode::MacOS);
+        state.settings_mut().set_tapping_term(230);
+        storage.save_state(&state).unwrap();
+        KEYMAP.set(1, 2, 0x07);
+        storage.save_keymap(&KEYMAP, &mut bytes).unwrap();
+        KEYMAP.reset();
+        // Unchanged records are not written again.
+        let head = storage.head;
+        storage.save_state(&state).unwrap();
+        assert_eq!(storage.head, head);
+
+        let mut storage = Storage::new(&mut flash, 0, 1024).unwrap();
+        let mut restored = State::new();
+        storage.load_state(&mut restored, 4).unwrap();
+        assert_eq!(restored, state);
+        assert_eq!(restored.settings().tapping_term(), 230);
+        assert_eq!(storage.load_keymap(&KEYMAP, &mut bytes), Ok(true));
+        assert_eq!(KEYMAP.get(1, 2), 0x07);
+
+        // Fill the bank a few times over.
+        for layer in 0..100 {
+            state.set_default_layer(layer % 4);
+            storage.save_state(&state).unwrap();
+        }
+        assert!(storage.generation > 1);
+        let mut storage = Storage::new(&mut flash, 0, 1024).unwrap();
+        storage.load_state(&mut restored, 4).unwrap();
+        assert_eq!(restored.default_layer(), 3);
+        let mut fewer = State::new();
+        storage.load_state(&mut fewer, 3).unwrap();
+        assert_eq!(fewer.default_layer(), 0);
+        KEYMAP.reset();
+        assert_eq!(storage.load_keymap(&KEYMAP, &mut bytes), Ok(true));
+        assert_eq!(KEYMAP.get(1, 2), 0x07);
+    }
+
+    #[test]
+    fn test_crc() {
+        let mut flash = Flash([ERASED; 1024]);
+        let mut storage = Storage::new(&mut flash, 0, 1024).unwrap();
+        storage.write(Record::DefaultLayer, 1, &[1]).unwrap();
+        storage.write(Record::DefaultLayer, 1, &[2]).unwrap();
+        let at = storage.latest[Record::DefaultLayer as usize].unwrap();
+        flash.0[at as usize + HEADER] = 0;
+
+        // The corrupted record is skipped.
+        let mut storage = Storage::new(&mut flash, 0, 1024).unwrap();
+        let mut state = State::new();
+        storage.load_state(&mut state, 4).unwrap();
+        assert_eq!(state.default_layer(), 1);
+
+        // Records of unknown versions are ignored.
+        storage.write(Record::Tunables, 9, &[0xFF; 5]).unwrap();
+        storage.load_state(&mut state, 4).unwrap();
+        assert_eq!(state.modes(), Modes::NONE);
+    }
+
+    #[test]
+    fn test_migration() {
+        let mut flash = Flash([ERASED; 1024]);
+        let mut storage = Storage::new(&mut flash, 0, 1024).unwrap();
+        let mut state = State::new();
+        state.settings_mut().set_tapping_term(180);
+
+        // Version 1 keeps the current timings.
+        storage
+            .write(Record::Tunables, 1, &[0x08, 0x00, 3])
+            .unwrap();
+        storage.load_state(&mut state, 4).unwrap();
+        assert_eq!(state.modes(), Modes::GAMING);
+        assert_eq!(state.unicode(), unicode::Mode::MacOS);
+        assert_eq!(state.settings().tapping_term(), 180);
+
+        // Saving writes the current version.
+        storage.save_state(&state).unwrap();
+        let mut bytes = [0; SMALL];
+        assert_eq!(storage.read(Record::Tunables, &mut bytes), Ok(Some((2, 8))));
+        assert_eq!(bytes[..8], [0x08, 0x00, 3, 180, 0, 50, 0, 5]);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::report::Keyboard;
+use crate::sequence::Sequence;
+
+// Host input method used to type code points.
+// Windows needs `EnableHexNumpad` set in the registry, macOS needs the
+// Unicode Hex Input source selected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Linux,
+    Windows,
+    WinCompose,
+    MacOS,
+}
+
+const DIGITS: [Keyboard; 16] = [
+    Keyboard::Keyboard0,
+    Keyboard::Keyboard1,
+    Keyboard::Keyboard2,
+    Keyboard::Keyboard3,
+    Keyboard::Keyboard4,
+    Keyboard::Keyboard5,
+    Keyboard::Keyboard6,
+    Keyboard::Keyboard7,
+    Keyboard::Keyboard8,
+    Keyboard::Keyboard9,
+    Keyboard::A,
+    Keyboard::B,
+    Keyboard::C,
+    Keyboard::D,
+    Keyboard::E,
+    Keyboard::F,
+];
+
+const KEYPAD_DIGITS: [Keyboard; 10] = [
+    Keyboard::Keypad0,
+    Keyboard::Keypad1,
+    Keyboard::Keypad2,
+    Keyboard::Keypad3,
+    Keyboard::Keypad4,
+    Keyboard::Keypad5,
+    Keyboard::Keypad6,
+    Keyboard::Keypad7,
+    Keyboard::Keypad8,
+    Keyboard::Keypad9,
+];
+
+pub fn sequence(mode: Mode, c: char, hold: usize) -> Sequence {
+    let mut sequence = Sequence::new(hold);
+    let code = c as u32;
+    match mode {
+        Mode::Linux => {
+            sequence.tap(
+                &[],
+                &[Keyboard::LeftControl, Keyboard::LeftShift, Keyboard::U],
+            );
+            hex(&mut sequence, &[], code, digits(code), &DIGITS[..10]);
+            sequence.tap(&[], &[Keyboard::Space]);
+        }
+        Mode::Windows => {
+            sequence.tap(&[Keyboard::LeftAlt], &[Keyboard::KeypadAdd]);
+            hex(
+                &mut sequence,
+                &[Keyboard::LeftAlt],
+                code,
+                digits(code),
+                &KEYPAD_DIGITS,
+            );
+            sequence.push(&[]);
+        }
+        Mode::WinCompose => {
+            sequence.tap(&[], &[Keyboard::RightAlt]);
+            sequence.tap(&[], &[Keyboard::U]);
+            hex(&mut sequence, &[], code, digits(code), &DIGITS[..10]);
+            sequence.tap(&[], &[Keyboard::ReturnEnter]);
+        }
+        Mode::MacOS => {
+            // Four digits per UTF-16 code unit.
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                hex(
+                    &mut sequence,
+                    &[Keyboard::LeftAlt],
+                    *unit as u32,
+                    4,
+                    &DIGITS[..10],
+                );
+            }
+            sequence.push(&[]);
+        }
+    }
+    sequence
+}
+
+fn digits(code: u32) -> usize {
+    (1..8).find(|n| code >> (4 * n) == 0).unwrap_or(8)
+}
+
+fn hex(sequence: &mut Sequence, held: &[Keyboard], code: u32, digits: usize, numbers: &[Keyboard]) {
+    for n in (0..digits).rev() {
+        let digit = (code >> (4 * n) & 0xF) as usize;
+        let key = numbers.get(digit).unwrap_or(&DIGITS[digit]);
+        sequence.tap(held, &[*key]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frames(mut sequence: Sequence) -> heapless::Vec<crate::sequence::Frame, 24> {
+        let mut frames = heapless::Vec::new();
+        while let Some(frame) = sequence.play() {
+            frames.push(frame.clone()).unwrap();
+        }
+        frames
+    }
+
+    #[test]
+    fn linux() {
+        let frames = frames(sequence(Mode::Linux, '—', 1));
+        assert_eq!(frames.len(), 12);
+        assert_eq!(
+            frames[0].as_slice(),
+            [Keyboard::LeftControl, Keyboard::LeftShift, Keyboard::U]
+        );
+        assert_eq!(frames[2].as_slice(), [Keyboard::Keyboard2]);
+        assert_eq!(frames[4].as_slice(), [Keyboard::Keyboard0]);
+        assert_eq!(frames[6].as_slice(), [Keyboard::Keyboard1]);
+        assert_eq!(frames[8].as_slice(), [Keyboard::Keyboard4]);
+        assert_eq!(frames[10].as_slice(), [Keyboard::Space]);
+    }
+
+    #[test]
+    fn windows() {
+        let frames = frames(sequence(Mode::Windows, 'é', 1));
+        assert_eq!(frames.len(), 7);
+        assert_eq!(
+            frames[0].as_slice(),
+            [Keyboard::LeftAlt, Keyboard::KeypadAdd]
+        );
+        assert_eq!(frames[2].as_slice(), [Keyboard::LeftAlt, Keyboard::E]);
+        assert_eq!(frames[4].as_slice(), [Keyboard::LeftAlt, Keyboard::Keypad9]);
+        assert!(frames[6].is_empty());
+    }
+
+    #[test]
+    fn macos() {
+        // U+1F600 is D83D DE00 in UTF-16.
+        let frames = frames(sequence(Mode::MacOS, '😀', 1));
+        assert_eq!(frames.len(), 17);
+        assert_eq!(frames[0].as_slice(), [Keyboard::LeftAlt, Keyboard::D]);
+        assert_eq!(frames[8].as_slice(), [Keyboard::LeftAlt, Keyboard::D]);
+        assert_eq!(frames[10].as_slice(), [Keyboard::LeftAlt, Keyboard::E]);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::command::{Packet, Transport};
+use crate::processor::dynamic::DynamicKeymap;
+
+pub const PROTOCOL_VERSION: u16 = 0x000C;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Command {
+    GetProtocolVersion = 0x01,
+    GetKeycode = 0x04,
+    SetKeycode = 0x05,
+    Reset = 0x06,
+    MacroGetCount = 0x0C,
+    MacroGetBufferSize = 0x0D,
+    GetLayerCount = 0x11,
+    GetBuffer = 0x12,
+    SetBuffer = 0x13,
+    Unhandled = 0xFF,
+}
+
+impl Command {
+    pub fn from_u8(id: u8) -> Command {
+        match id {
+            0x01 => Command::GetProtocolVersion,
+            0x04 => Command::GetKeycode,
+            0x05 => Command::SetKeycode,
+            0x06 => Command::Reset,
+            0x0C => Command::MacroGetCount,
+            0x0D => Command::MacroGetBufferSize,
+            0x11 => Command::GetLayerCount,
+            0x12 => Command::GetBuffer,
+            0x13 => Command::SetBuffer,
+            _ => Command::Unhandled,
+        }
+    }
+}
+
+// VIA raw HID protocol over a `DynamicKeymap` laid out in `rows` by `cols`.
+// Responses are the requests with the queried values filled in, and the
+// stored ones for sets.
+// The keymap buffer holds big-endian keycodes, layer by layer, row by row.
+pub struct Via<const N: usize, const L: usize> {
+    keymap: &'static DynamicKeymap<N, L>,
+    cols: usize,
+}
+
+impl<const N: usize, const L: usize> Via<N, L> {
+    pub const fn new(keymap: &'static DynamicKeymap<N, L>, cols: usize) -> Via<N, L> {
+        Via { keymap, cols }
+    }
+
+    fn position(&self, layer: u8, row: u8, col: u8) -> Option<(usize, usize)> {
+        let (row, col) = (row as usize, col as usize);
+        let i = row * self.cols + col;
+        (col < self.cols && i < N && (layer as usize) < L).then_some((layer as usize, i))
+    }
+
+    pub fn respond(&self, request: &Packet) -> Packet {
+        let mut response = *request;
+        match Command::from_u8(request[0]) {
+            Command::GetProtocolVersion => {
+                response[1..3].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+            }
+            Command::GetKeycode => {
+                if let Some((layer, i)) = self.position(request[1], request[2], request[3]) {
+                    response[4..6].copy_from_slice(&self.keymap.get(layer, i).to_be_bytes());
+                }
+            }
+            Command::SetKeycode => {
+                if let Some((layer, i)) = self.position(request[1], request[2], request[3]) {
+                    let code = u16::from_be_bytes([request[4], request[5]]);
+                    let code = self.keymap.set(layer, i, code);
+                    response[4..6].copy_from_slice(&code.to_be_bytes());
+                }
+            }
+            Command::Reset => self.keymap.reset(),
+            // No dynamic macros.
+            Command::MacroGetCount => response[1] = 0,
+            Command::MacroGetBufferSize => response[1..3].fill(0),
+            Command::GetLayerCount => response[1] = L as u8,
+            Command::GetBuffer | Command::SetBuffer => {
+                let offset = u16::from_be_bytes([request[1], request[2]]) as usize;
+                let size = (request[3] as usize).min(response.len() - 4);
+                let end = (offset + size).min(N * L * 2);
+                let set = request[0] == Command::SetBuffer as u8;
+                // Keycodes are written whole, once both their bytes are known.
+                for key in offset / 2..end.div_ceil(2) {
+                    let (layer, i) = (key / N, key % N);
+                    let mut code = self.keymap.get(layer, i).to_be_bytes();
+                    let bytes = (key * 2).max(offset)..(key * 2 + 2).min(end);
+                    if set {
+                        for byte in bytes.clone() {
+                            code[byte % 2] = response[4 + byte - offset];
+                        }
+                        code = self
+                            .keymap
+                            .set(layer, i, u16::from_be_bytes(code))
+                            .to_be_bytes();
+                    }
+                    // Sets echo what was stored.
+                    for byte in bytes {
+                        response[4 + byte - offset] = code[byte % 2];
+                    }
+                }
+            }
+            Command::Unhandled => response[0] = Command::Unhandled as u8,
+        }
+        response
+    }
+
+    // Answers one pending request from the host, if any.
+    pub fn serve(&self, transport: &mut impl Transport) {
+        if let Some(request) = transport.receive() {
+            transport.send(&self.respond(&request));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use heapless::spsc::Queue;
+
+    use super::*;
+    use crate::command::PACKET_SIZE;
+    use crate::keymap::{BasicKeymap, Keymap};
+    use crate::processor::dynamic::DynamicKeys;
+    use crate::processor::Process;
+    use crate::report::{Keyboard, Report};
+
+    static DEFAULTS: [[u16; 4]; 2] = [[0x04, 0x05, 0x06, 0x5221], [0x01, 0x01, 0x1E, 0x01]];
+    static KEYMAP: DynamicKeymap<4, 2> = DynamicKeymap::new(&DEFAULTS);
+    static DK: DynamicKeys<4, 2> = DynamicKeys::new(&KEYMAP);
+    static HANDLERS: [&'static dyn Process<4, 2>; 1] = [&DK];
+    static VIA: Via<4, 2> = Via::new(&KEYMAP, 2);
+    static mut Q: Queue<Report, 128> = Queue::new();
+
+    fn request(bytes: &[u8]) -> Packet {
+        let mut packet = [0; PACKET_SIZE];
+        packet[..bytes.len()].copy_from_slice(bytes);
+        VIA.respond(&packet)
+    }
+
+    #[test]
+    fn test_via() {
+        assert_eq!(request(&[0x01])[..3], [0x01, 0x00, 0x0C]);
+        assert_eq!(request(&[0x11])[..2], [0x11, 2]);
+        assert_eq!(request(&[0x04, 0, 1, 0])[4..6], [0x00, 0x06]);
+        assert_eq!(request(&[0x42])[0], 0xFF);
+
+        let (producer, mut consumer) = unsafe { (*core::ptr::addr_of_mut!(Q)).split() };
+        let mut keymap: BasicKeymap<4, 2> = BasicKeymap::new(&HANDLERS, producer);
+        let mut output = |switches: [bool; 4]| {
+            (0..6).for_each(|_| keymap.tick(&switches));
+            let mut last = None;
+            while let Some(report) = consumer.dequeue() {
+                last = Some(report);
+            }
+            last
+        };
+
+        // Layer 1 is transparent but for key 2.
+        assert_eq!(output([true, false, false, true]), Some(r(Keyboard::A)));
+        assert_eq!(
+            output([false, false, true, true]),
+            Some(r(Keyboard::Keyboard1))
+        );
+        output([false; 4]);
+
+        // Remap key 1 on layer 0 to B.
+        request(&[0x05, 0, 0, 1, 0x00, 0x05]);
+        assert_eq!(output([false, true, false, false]), Some(r(Keyboard::B)));
+        output([false; 4]);
+
+        let buffer = request(&[0x12, 0x00, 0x00, 8]);
+        assert_eq!(
+            buffer[4..12],
+            [0x00, 0x04, 0x00, 0x05, 0x00, 0x06, 0x52, 0x21]
+        );
+        request(&[0x13, 0x00, 0x02, 2, 0x00, 0x07]);
+        assert_eq!(request(&[0x04, 0, 0, 1])[4..6], [0x00, 0x07]);
+        request(&[0x13, 0x00, 0x03, 3, 0x08, 0x52, 0x20]);
+        assert_eq!(request(&[0x04, 0, 0, 1])[4..6], [0x00, 0x08]);
+        assert_eq!(request(&[0x04, 0, 1, 0])[4..6], [0x52, 0x20]);
+
+        // MO(2) is past the layers, sets echo the keycode kept.
+        assert_eq!(request(&[0x05, 0, 1, 0, 0x52, 0x22])[4..6], [0x52, 0x20]);
+        assert_eq!(request(&[0x05, 0, 1, 1, 0x00, 0x04])[4..6], [0x00, 0x04]);
+        assert_eq!(
+            request(&[0x13, 0x00, 0x03, 3, 0x09, 0x52, 0x22])[4..7],
+            [0x09, 0x52, 0x20]
+        );
+        assert_eq!(request(&[0x04, 0, 0, 1])[4..6], [0x00, 0x09]);
+        assert_eq!(request(&[0x04, 0, 1, 0])[4..6], [0x52, 0x20]);
+
+        request(&[0x06]);
+        assert_eq!(request(&[0x04, 0, 0, 1])[4..6], [0x00, 0x05]);
+    }
+
+    fn r(key: Keyboard) -> Report {
+        Report::Keyboard(key)
+    }
+}